@@ -0,0 +1,82 @@
+// 分层配置：默认值 < TOML 配置文件 < 环境变量
+//
+// 环境变量以 PANORAMA_C__ 为前缀，用 __ 分隔层级，例如：
+//   PANORAMA_C__WS_CLIENT__URL=ws://10.0.0.2:8080
+//   PANORAMA_C__WS_CLIENT__PING_INTERVAL_SECS=30
+use anyhow::{ensure, Result};
+use panorama_utils::config::{self, ConfigSource};
+use serde::Deserialize;
+use std::path::Path;
+
+/// 默认配置文件（相对于工作目录）
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+/// 指定配置文件路径的环境变量
+pub const CONFIG_PATH_ENV: &str = "PANORAMA_C_CONFIG";
+/// 覆盖单个配置项的环境变量前缀
+pub const ENV_PREFIX: &str = "PANORAMA_C__";
+
+const SOURCE: ConfigSource = ConfigSource {
+    default_path: DEFAULT_CONFIG_PATH,
+    path_env: CONFIG_PATH_ENV,
+    env_prefix: ENV_PREFIX,
+};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub log: LogSettings,
+    pub ws_client: WsClientSettings,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// log4rs 配置文件路径
+    pub config_path: String,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            config_path: "log4rs.yaml".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WsClientSettings {
+    /// web socket 服务端地址
+    pub url: String,
+    /// 心跳间隔（秒），必须大于 0
+    pub ping_interval_secs: u64,
+}
+
+impl Default for WsClientSettings {
+    fn default() -> Self {
+        Self {
+            url: "ws://127.0.0.1:8080".to_string(),
+            ping_interval_secs: 5,
+        }
+    }
+}
+
+impl Settings {
+    /// 加载配置。
+    /// path 为 None 时依次尝试 PANORAMA_C_CONFIG 和 config.toml，文件不存在则只用默认值。
+    /// 显式指定的文件不存在时报错。
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let settings: Self = config::load(&SOURCE, path)?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    // 反序列化之外的取值检查
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            self.ws_client.ping_interval_secs > 0,
+            "invalid settings: ws_client.ping_interval_secs must be greater than 0"
+        );
+        Ok(())
+    }
+}
@@ -1 +1,13 @@
-pub const LOG4RS_YAML_PATH: &str = "/Users/zongge/rust/panorama/panorama_s/log4rs.yaml";
\ No newline at end of file
+use crate::common::config::Settings;
+use anyhow::Result;
+use panorama_utils::config::GlobalSettings;
+
+pub static SETTINGS: GlobalSettings<Settings> = GlobalSettings::new();
+
+pub fn init_settings(settings: Settings) -> Result<()> {
+    SETTINGS.init(settings)
+}
+/// 取得全局配置，未初始化时使用默认值
+pub fn settings() -> &'static Settings {
+    SETTINGS.get()
+}
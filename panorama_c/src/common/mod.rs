@@ -1 +1,2 @@
-pub mod global;
\ No newline at end of file
+pub mod config;
+pub mod global;
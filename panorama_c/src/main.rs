@@ -3,21 +3,37 @@
 mod common;
 mod web_socket;
 
+use crate::common::config::Settings;
 use crate::common::global;
 // use crate::web_socket::use_ws_client;
 use crate::web_socket::ws_client_1;
 use anyhow::Result;
 use log::{error, info};
-use log4rs;
-use rust_utils::graceful_shutdown;
+use panorama_utils::logging;
+use panorama_utils::shutdown::{graceful_shutdown, ShutdownToken};
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() {
     println!("Hello, world!");
 
+    // 加载配置
+    let settings = match Settings::load(None) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("load settings Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let log_config_path = settings.log.config_path.clone();
+    if let Err(e) = global::init_settings(settings) {
+        eprintln!("init settings Error: {}", e);
+    }
+
     // 初始化日志系统
-    if let Err(e) = log4rs::init_file(global::LOG4RS_YAML_PATH, Default::default()) {
-        eprintln!("init log4rs Error: {}", e);
+    // 客户端不提供运行时调整级别的接口，不需要保留 LogControl
+    if let Err(e) = logging::init_logging(&log_config_path, None) {
+        eprintln!("init log4rs Error: {:#}", e);
     }
     info!("init log4rs ok.");
 
@@ -26,8 +42,10 @@ async fn main() {
         Err(e) => error!("[init] failed: {}", e),
     }
 
-    let _ = tokio::spawn(async {
-        if let Err(e) = ws_client_1::ws_client_sample().await {
+    let shutdown = ShutdownToken::new();
+    let token = shutdown.clone();
+    let client = tokio::spawn(async move {
+        if let Err(e) = ws_client_1::ws_client_sample(token).await {
             eprintln!("ws_client_sample error: {}", e);
         }
     });
@@ -40,10 +58,16 @@ async fn main() {
    
    info!("wait to close.");
 
-    match graceful_shutdown().await {
-        Ok(()) => info!("Shutdown successful"),
+    match graceful_shutdown(&shutdown).await {
+        Ok(reason) => info!("Shutdown successful ({})", reason),
         Err(e) => error!("Shutdown failed: {}", e),
     }
+
+    // 等待客户端发送 Close 后退出
+    shutdown.cancel();
+    if tokio::time::timeout(Duration::from_secs(5), client).await.is_err() {
+        error!("ws client did not close in time");
+    }
 }
 
 fn init() -> Result<()> {
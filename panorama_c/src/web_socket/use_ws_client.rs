@@ -1,4 +1,5 @@
 use log::{error, info};
+use crate::common::global;
 use crate::web_socket::ws_client;
 use std::error::Error;
 use anyhow::Result;
@@ -8,7 +9,7 @@ pub async fn use_ws()  -> Result<(), Box<dyn Error>> {
     let mut client = ws_client::WebSocketClient::new();
     
     // 连接服务器
-    client.connect("ws://127.0.0.1:8080").await?;
+    client.connect(&global::settings().ws_client.url).await?;
     info!("Connected to WebSocket server");
 
     // 发送消息
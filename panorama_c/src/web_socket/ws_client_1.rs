@@ -1,15 +1,18 @@
 // 客户端
+use crate::common::global;
 use anyhow::Result;
 use futures_util::{SinkExt, StreamExt};
 use log::{error, info};
+use panorama_utils::shutdown::ShutdownToken;
 use std::error::Error;
 use tokio::time::{interval, Duration};
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::Message;
 use url::Url;
 
-pub async fn ws_client_sample() -> Result<(), Box<dyn Error>> {
-    let url = Url::parse("ws://127.0.0.1:8080")?;
+pub async fn ws_client_sample(shutdown: ShutdownToken) -> Result<(), Box<dyn Error>> {
+    let settings = &global::settings().ws_client;
+    let url = Url::parse(&settings.url)?;
 
     info!("Connecting to {}", url);
     let (ws_stream, _) = connect_async(url).await?;
@@ -17,8 +20,8 @@ pub async fn ws_client_sample() -> Result<(), Box<dyn Error>> {
 
     let (mut write, mut read) = ws_stream.split();
 
-    // 创建心跳定时器（每30秒发送Ping）
-    let mut ping_interval = interval(Duration::from_secs(5));
+    // 创建心跳定时器（按配置间隔发送Ping）
+    let mut ping_interval = interval(Duration::from_secs(settings.ping_interval_secs));
 
     // 使用有界通道处理背压
     let (tx, mut rx) = tokio::sync::mpsc::channel::<Message>(32);
@@ -48,6 +51,8 @@ pub async fn ws_client_sample() -> Result<(), Box<dyn Error>> {
                     }
                     info!("send ping")
                 },
+                // 收到关闭通知后退出循环，发送 Close
+                _ = shutdown.cancelled() => break,
             }
         }
 
@@ -0,0 +1,214 @@
+// 命令行参数与子命令
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use log::{info, LevelFilter};
+use panorama_s::common::error::AppError;
+use panorama_s::common::global;
+use panorama_s::common::secret::Secret;
+use panorama_s::sqlite_sample::sqlite_c::SqliteCrud;
+use panorama_s::sqlite_sample::user_service::{NewUser, UserService, UserUpdate};
+use panorama_s::sqlite_sample::users_po::UserStatus;
+use panorama_s::sqlite_sample::{backup, migrate};
+use panorama_s::use_sqlite;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Parser)]
+#[command(name = "panorama_s", version, about = "panorama server")]
+pub struct Cli {
+    /// 配置文件路径（默认 config.toml）
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// 日志级别：off/error/warn/info/debug/trace，覆盖配置文件
+    #[arg(long, global = true)]
+    pub log_level: Option<LevelFilter>,
+
+    /// 不指定时等同于 serve
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// 启动 web server 和 web socket server
+    Serve {
+        /// 启动前用指定的备份替换数据库（文件路径或备份目录中的文件名）
+        #[arg(long, value_name = "BACKUP")]
+        restore: Option<PathBuf>,
+    },
+    /// 执行数据库迁移
+    Migrate,
+    /// 读写 table_test 中的键值
+    Kv {
+        #[command(subcommand)]
+        command: KvCommand,
+    },
+    /// 在线备份数据库
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// 管理 users 表
+    Users {
+        #[command(subcommand)]
+        command: UsersCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum KvCommand {
+    /// 读取一个键
+    Get { key: String },
+    /// 写入一个键（已存在则覆盖）
+    Set { key: String, value: String },
+    /// 删除一个键
+    Delete { key: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BackupCommand {
+    /// 备份到 backup.dir，并清理超出 backup.keep 的旧备份
+    Create,
+    /// 列出已有的备份，最新的在前
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UsersCommand {
+    /// 列出所有用户
+    List,
+    /// 新增用户
+    Add {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        email: Option<String>,
+        /// 显示名，不填时与 username 相同
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        age: i32,
+        /// 登录密码，不填时用户不能登录
+        #[arg(long, env = "PANORAMA_S_USER_PASSWORD", hide_env_values = true)]
+        password: Option<Secret>,
+    },
+    /// 重设密码
+    Passwd {
+        id: i64,
+        #[arg(long, env = "PANORAMA_S_USER_PASSWORD", hide_env_values = true)]
+        password: Secret,
+    },
+    /// 停用用户
+    Disable { id: i64 },
+    /// 启用用户
+    Enable { id: i64 },
+    /// 按 id 删除用户
+    Delete { id: i64 },
+}
+
+pub fn run_migrate(db: &SqliteCrud) -> Result<()> {
+    let applied = migrate::apply(db)?;
+    for m in &applied {
+        println!("applied {:04} {}", m.version, m.name);
+    }
+
+    info!("[cli] migrate ok. applied:{}", applied.len());
+    println!("schema version {}", migrate::latest_version());
+    Ok(())
+}
+
+pub fn run_kv(db: &SqliteCrud, command: KvCommand) -> Result<()> {
+    match command {
+        KvCommand::Get { key } => match use_sqlite::query_data(db, &key) {
+            Ok(value) => println!("{}", value),
+            Err(AppError::NotFound(_)) => println!("(nil)"),
+            Err(e) => return Err(e.into()),
+        },
+        KvCommand::Set { key, value } => {
+            use_sqlite::insert_data(db, &key, &value)?;
+            println!("OK");
+        }
+        KvCommand::Delete { key } => {
+            let deleted = use_sqlite::delete_data(db, &key)?;
+            println!("deleted {}", deleted);
+        }
+    }
+    Ok(())
+}
+
+pub fn create_backup(db: &SqliteCrud) -> Result<()> {
+    let report = backup::create(db, &global::settings().backup)?;
+    println!("{}", report.backup.path);
+    for name in &report.removed {
+        println!("removed {}", name);
+    }
+    Ok(())
+}
+
+pub fn list_backups() -> Result<()> {
+    let settings = global::settings();
+    for file in backup::list(&settings.sqlite.db_path, &settings.backup)? {
+        println!("{}\t{}", file.name, file.size_bytes);
+    }
+    Ok(())
+}
+
+pub fn run_users(db: Arc<SqliteCrud>, command: UsersCommand) -> Result<()> {
+    let users = UserService::new(db);
+
+    match command {
+        UsersCommand::List => {
+            for user in users.list()? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    user.id,
+                    user.username,
+                    user.email.as_deref().unwrap_or("-"),
+                    user.name,
+                    user.age,
+                    user.status.as_str()
+                );
+            }
+        }
+        UsersCommand::Add {
+            username,
+            email,
+            name,
+            age,
+            password,
+        } => {
+            let user = users.create(NewUser {
+                username,
+                email,
+                name,
+                age,
+                password,
+            })?;
+            println!("OK id={}", user.id);
+        }
+        UsersCommand::Passwd { id, password } => {
+            users.update(
+                id,
+                UserUpdate {
+                    password: Some(password),
+                    ..Default::default()
+                },
+            )?;
+            println!("OK");
+        }
+        UsersCommand::Disable { id } => {
+            users.set_status(id, UserStatus::Disabled)?;
+            println!("OK");
+        }
+        UsersCommand::Enable { id } => {
+            users.set_status(id, UserStatus::Active)?;
+            println!("OK");
+        }
+        UsersCommand::Delete { id } => {
+            users.delete(id)?;
+            println!("OK");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+// 时间工具，数据库中的时间统一存 unix 毫秒
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 当前时间，unix 毫秒
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+/// unix 毫秒转成 UTC 时间 20261018-091234-567，可以按字符串排序，用于文件名
+pub fn format_compact(ms: i64) -> String {
+    let secs = ms.div_euclid(1000);
+    let millis = ms.rem_euclid(1000);
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let rem = secs.rem_euclid(86_400);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        millis
+    )
+}
+
+// 1970-01-01 之后的天数转成公历日期（Howard Hinnant 的 civil_from_days 算法）
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_from_days_handles_epoch_leap_years_and_negatives() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(59), (1970, 3, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+        assert_eq!(civil_from_days(20_744), (2026, 10, 18));
+        assert_eq!(civil_from_days(-719_468), (0, 3, 1));
+    }
+
+    #[test]
+    fn format_compact_is_sortable_utc() {
+        assert_eq!(format_compact(0), "19700101-000000-000");
+        assert_eq!(format_compact(1_792_316_959_512), "20261018-094919-512");
+        // 1969-12-31 23:59:59.999
+        assert_eq!(format_compact(-1), "19691231-235959-999");
+        assert!(format_compact(999) < format_compact(1_000));
+    }
+}
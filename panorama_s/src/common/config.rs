@@ -0,0 +1,231 @@
+// 分层配置：默认值 < TOML 配置文件 < 环境变量
+//
+// 环境变量以 PANORAMA_S__ 为前缀，用 __ 分隔层级，例如：
+//   PANORAMA_S__SQLITE__DB_PATH=/var/lib/panorama/panorama.db
+//   PANORAMA_S__WEB_SERVER__LISTEN='["0.0.0.0:3000", "unix:/run/panorama/web.sock"]'
+use crate::common::listen::ListenAddr;
+use crate::common::secret::Secret;
+use anyhow::Result;
+use log::LevelFilter;
+use panorama_utils::config::{self, ConfigSource};
+use serde::Deserialize;
+use std::path::Path;
+
+/// 默认配置文件（相对于工作目录）
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+/// 指定配置文件路径的环境变量
+pub const CONFIG_PATH_ENV: &str = "PANORAMA_S_CONFIG";
+/// 覆盖单个配置项的环境变量前缀
+pub const ENV_PREFIX: &str = "PANORAMA_S__";
+
+const SOURCE: ConfigSource = ConfigSource {
+    default_path: DEFAULT_CONFIG_PATH,
+    path_env: CONFIG_PATH_ENV,
+    env_prefix: ENV_PREFIX,
+};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub log: LogSettings,
+    pub sqlite: SqliteSettings,
+    pub kv: KvSettings,
+    pub changes: ChangesSettings,
+    pub backup: BackupSettings,
+    pub admin: AdminSettings,
+    pub web_server: WebServerSettings,
+    pub ws_server: WsServerSettings,
+    pub supervisor: SupervisorSettings,
+    pub shutdown: ShutdownSettings,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// log4rs 配置文件路径
+    pub config_path: String,
+    /// 覆盖 log4rs 配置中的 root 级别
+    pub level: Option<LevelFilter>,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            config_path: "log4rs.yaml".to_string(),
+            level: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SqliteSettings {
+    /// sqlite 数据库文件路径
+    pub db_path: String,
+    /// 只读连接数量，写连接固定为一个
+    pub read_pool_size: usize,
+    /// 等待空闲连接的最长时间，超时返回 503
+    pub acquire_timeout_ms: u64,
+    /// 数据库被其他连接锁住时 sqlite 内部重试的时间
+    pub busy_timeout_ms: u64,
+    /// 启动时自动执行迁移；为 false 时只检查版本，不一致则拒绝启动
+    pub auto_migrate: bool,
+    /// 执行时间达到该值（毫秒）的语句记 warn 日志，0 表示不记录
+    pub slow_query_ms: u64,
+    /// 每个连接缓存的预编译语句数量
+    pub statement_cache_capacity: usize,
+}
+
+impl Default for SqliteSettings {
+    fn default() -> Self {
+        Self {
+            db_path: "src/sqlite_sample/sqlite_sample.db".to_string(),
+            read_pool_size: 4,
+            acquire_timeout_ms: 5000,
+            busy_timeout_ms: 5000,
+            auto_migrate: true,
+            slow_query_ms: 100,
+            statement_cache_capacity: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct KvSettings {
+    /// 清理过期 key 的间隔（秒），0 表示不启动清理服务（过期的 key 仍然读不到）
+    pub expiry_interval_secs: u64,
+}
+
+impl Default for KvSettings {
+    fn default() -> Self {
+        Self {
+            expiry_interval_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ChangesSettings {
+    /// 轮询 changes 表的间隔（毫秒），0 表示不启动 change_feed 服务（变更仍然会记录）
+    pub poll_interval_ms: u64,
+    /// changes 表中记录保留的时间（秒），由 changes_prune 服务清理，不受 poll_interval_ms 影响
+    pub retention_secs: u64,
+}
+
+impl Default for ChangesSettings {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: 200,
+            retention_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    /// 备份文件目录，不存在时自动创建
+    pub dir: String,
+    /// 保留最近的备份数量，0 表示全部保留
+    pub keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            dir: "backups".to_string(),
+            keep: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AdminSettings {
+    /// /admin 接口的访问密钥，请求头 Authorization: Bearer <auth_key>。
+    /// 不配置时 /admin 接口全部拒绝访问。
+    pub auth_key: Option<Secret>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WebServerSettings {
+    /// http 监听地址列表，支持 IPv4、[IPv6]:port 和 unix:/path
+    pub listen: Vec<ListenAddr>,
+}
+
+impl Default for WebServerSettings {
+    fn default() -> Self {
+        Self {
+            listen: vec!["127.0.0.1:3000".parse().expect("valid default address")],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WsServerSettings {
+    /// 是否启动独立的 web socket 端口；关闭后只能通过 web server 的 /ws 连接
+    pub enabled: bool,
+    /// web socket 监听地址列表，支持 IPv4、[IPv6]:port 和 unix:/path
+    pub listen: Vec<ListenAddr>,
+}
+
+impl Default for WsServerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            listen: vec!["127.0.0.1:8080".parse().expect("valid default address")],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SupervisorSettings {
+    /// 连续重启的最大次数，超过后放弃
+    pub max_restarts: u32,
+    /// 第一次重启前的等待时间（毫秒），之后每次翻倍
+    pub initial_backoff_ms: u64,
+    /// 重启等待时间上限（毫秒）
+    pub max_backoff_ms: u64,
+    /// 服务稳定运行超过该时间（秒）后重新计算重启次数
+    pub reset_after_secs: u64,
+}
+
+impl Default for SupervisorSettings {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            reset_after_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ShutdownSettings {
+    /// 收到退出信号后等待服务排空的最长时间（秒）
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for ShutdownSettings {
+    fn default() -> Self {
+        Self {
+            drain_timeout_secs: 10,
+        }
+    }
+}
+
+impl Settings {
+    /// 加载配置。
+    /// path 为 None 时依次尝试 PANORAMA_S_CONFIG 和 config.toml，文件不存在则只用默认值。
+    /// 显式指定的文件不存在时报错。
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        config::load(&SOURCE, path)
+    }
+}
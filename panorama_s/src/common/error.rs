@@ -0,0 +1,101 @@
+// 应用错误类型，http 处理器直接返回 AppResult，由 IntoResponse 转成统一的 json 错误
+use crate::web_server::request_id;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use log::error;
+use rusqlite::ErrorCode;
+use serde_json::json;
+
+pub type AppResult<T> = std::result::Result<T, AppError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("validation failed: {0}")]
+    Validation(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("unavailable: {0}")]
+    Unavailable(String),
+    // 消息里已经包含底层错误，不再标记 source，避免 {:#} 打印两遍
+    #[error("database error: {0}")]
+    Database(rusqlite::Error),
+    #[error("internal error: {0:#}")]
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> Self {
+        AppError::Internal(e)
+    }
+}
+
+impl AppError {
+    /// 错误码，放在响应 json 的 code 字段
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Validation(_) => "validation",
+            AppError::Conflict(_) => "conflict",
+            AppError::Unavailable(_) => "unavailable",
+            AppError::Database(_) => "database",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+// 无结果映射为 NotFound，唯一约束冲突映射为 Conflict，其余都是 Database
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("record not found".into()),
+            rusqlite::Error::SqliteFailure(err, msg)
+                if err.code == ErrorCode::ConstraintViolation
+                    && msg.as_deref().is_some_and(|m| m.starts_with("UNIQUE")) =>
+            {
+                AppError::Conflict(msg.unwrap_or_default())
+            }
+            e => AppError::Database(e),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let request_id = request_id::current().unwrap_or_default();
+        let status = self.status();
+        // 数据库和内部错误只记日志，不把细节返回给客户端
+        let message = match &self {
+            AppError::Database(_) | AppError::Internal(_) => {
+                error!("[{}] {}", request_id, self);
+                status
+                    .canonical_reason()
+                    .unwrap_or("internal error")
+                    .to_string()
+            }
+            _ => self.to_string(),
+        };
+        let body = json!({
+            "code": self.code(),
+            "message": message,
+            "request_id": request_id,
+        });
+        (status, Json(body)).into_response()
+    }
+}
@@ -1,23 +1,32 @@
-use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use crate::common::config::Settings;
+use crate::common::supervisor::ServiceRegistry;
 use anyhow::Result;
 use once_cell::sync::OnceCell;
-use std::sync::{Arc, Mutex};
+use panorama_utils::config::GlobalSettings;
+use panorama_utils::logging::LogControl;
 
-pub static GLOBAL_DB: OnceCell<Arc<Mutex<SqliteCrud>>> = OnceCell::new();
-pub const SQLITE_DB_PATH: &str =
-    "/Users/zongge/rust/panorama/panorama_s/src/sqlite_sample/sqlite_sample.db";
-pub const LOG4RS_YAML_PATH: &str = "/Users/zongge/rust/panorama/panorama_s/log4rs.yaml";
+pub static SETTINGS: GlobalSettings<Settings> = GlobalSettings::new();
+pub static SERVICES: OnceCell<ServiceRegistry> = OnceCell::new();
+pub static LOG_CONTROL: OnceCell<LogControl> = OnceCell::new();
 
-pub fn init_global_db(db_path: &str) -> Result<()> {
-    let db = SqliteCrud::new(db_path)?;
-    GLOBAL_DB
-        .set(Arc::new(Mutex::new(db)))
-        .map_err(|_| anyhow::anyhow!("GLOBAL_DB already initialized"))?;
+pub fn init_settings(settings: Settings) -> Result<()> {
+    SETTINGS.init(settings)
+}
+/// 取得全局配置，未初始化时使用默认值
+pub fn settings() -> &'static Settings {
+    SETTINGS.get()
+}
+
+pub fn init_services(registry: ServiceRegistry) -> Result<()> {
+    SERVICES
+        .set(registry)
+        .map_err(|_| anyhow::anyhow!("SERVICES already initialized"))?;
     Ok(())
 }
-pub fn get_global_db() -> Result<Arc<Mutex<SqliteCrud>>> {
-    GLOBAL_DB
-        .get()
-        .map(Arc::clone)
-        .ok_or_else(|| anyhow::anyhow!("GLOBAL_DB not initialized"))
+
+pub fn init_log_control(control: LogControl) -> Result<()> {
+    LOG_CONTROL
+        .set(control)
+        .map_err(|_| anyhow::anyhow!("LOG_CONTROL already initialized"))?;
+    Ok(())
 }
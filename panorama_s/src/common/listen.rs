@@ -0,0 +1,357 @@
+// 监听地址：IPv4、IPv6（[::1]:3000）以及 unix:/path 形式的 Unix domain socket
+use anyhow::{Context, Result};
+use futures::stream::{self, BoxStream};
+use serde::Deserialize;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context as TaskContext, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+const UNIX_PREFIX: &str = "unix:";
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(path) = s.strip_prefix(UNIX_PREFIX) {
+            anyhow::ensure!(!path.is_empty(), "empty unix socket path");
+            return Ok(ListenAddr::Unix(PathBuf::from(path)));
+        }
+        let addr = s
+            .parse()
+            .with_context(|| format!("invalid listen address {}", s))?;
+        Ok(ListenAddr::Tcp(addr))
+    }
+}
+
+impl TryFrom<String> for ListenAddr {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "{}", addr),
+            ListenAddr::Unix(path) => write!(f, "{}{}", UNIX_PREFIX, path.display()),
+        }
+    }
+}
+
+/// 已绑定的监听器。测试时可以先绑定 127.0.0.1:0 再用 From 转换传进来。
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        /// 由 bind 创建的 socket 文件，drop 时删除
+        path: Option<PathBuf>,
+    },
+}
+
+impl Listener {
+    pub async fn bind(addr: &ListenAddr) -> Result<Self> {
+        match addr {
+            ListenAddr::Tcp(socket_addr) => {
+                let listener = TcpListener::bind(socket_addr)
+                    .await
+                    .with_context(|| format!("bind {} failed", addr))?;
+                Ok(Listener::Tcp(listener))
+            }
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                remove_stale_socket(path).with_context(|| format!("bind {} failed", addr))?;
+                let listener =
+                    UnixListener::bind(path).with_context(|| format!("bind {} failed", addr))?;
+                Ok(Listener::Unix {
+                    listener,
+                    path: Some(path.clone()),
+                })
+            }
+            #[cfg(not(unix))]
+            ListenAddr::Unix(_) => anyhow::bail!("unix socket is not supported: {}", addr),
+        }
+    }
+
+    /// 绑定一组地址，任何一个失败都返回错误
+    pub async fn bind_all(addrs: &[ListenAddr]) -> Result<Vec<Self>> {
+        anyhow::ensure!(!addrs.is_empty(), "no listen address configured");
+        let mut listeners = Vec::with_capacity(addrs.len());
+        for addr in addrs {
+            listeners.push(Listener::bind(addr).await?);
+        }
+        Ok(listeners)
+    }
+
+    pub fn local_addr(&self) -> String {
+        match self {
+            Listener::Tcp(listener) => listener
+                .local_addr()
+                .map(|a| a.to_string())
+                .unwrap_or_else(|_| "tcp:?".to_string()),
+            #[cfg(unix)]
+            Listener::Unix { listener, .. } => listener
+                .local_addr()
+                .ok()
+                .and_then(|a| a.as_pathname().map(|p| format!("{}{}", UNIX_PREFIX, p.display())))
+                .unwrap_or_else(|| "unix:?".to_string()),
+        }
+    }
+
+    /// 接受一个连接，返回连接和对端描述
+    pub async fn accept(&self) -> io::Result<(Stream, String)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                Ok((Stream::Tcp(stream), peer.to_string()))
+            }
+            #[cfg(unix)]
+            Listener::Unix { listener, .. } => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Stream::Unix(stream), self.local_addr()))
+            }
+        }
+    }
+
+    /// 转成连接流，供 hyper 和多监听器合并使用
+    pub fn into_stream(self) -> BoxStream<'static, io::Result<(Stream, String)>> {
+        Box::pin(stream::unfold(self, |listener| async move {
+            let accepted = listener.accept().await;
+            Some((accepted, listener))
+        }))
+    }
+}
+
+/// accept 的错误是否说明监听器本身已经不可用。
+/// 文件描述符耗尽（EMFILE、ENFILE）、连接在 accept 前被对端中止（ECONNABORTED）等都是暂时的，
+/// 调用方应当记录后继续接收。
+pub fn is_fatal_accept_error(e: &io::Error) -> bool {
+    // EINVAL：socket 已经不在监听状态
+    if e.kind() == io::ErrorKind::InvalidInput {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        matches!(e.raw_os_error(), Some(libc::EBADF | libc::ENOTSOCK))
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+// 上次进程异常退出时可能残留 socket 文件，只删除没有进程在监听的 socket，
+// 路径上是普通文件、目录等其他东西时报错，避免误删
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("stat {} failed", path.display())),
+    };
+    anyhow::ensure!(
+        metadata.file_type().is_socket(),
+        "{} exists and is not a unix socket",
+        path.display()
+    );
+    anyhow::ensure!(
+        std::os::unix::net::UnixStream::connect(path).is_err(),
+        "{} is in use by another process",
+        path.display()
+    );
+    std::fs::remove_file(path)
+        .with_context(|| format!("remove stale socket {} failed", path.display()))
+}
+
+impl From<TcpListener> for Listener {
+    fn from(listener: TcpListener) -> Self {
+        Listener::Tcp(listener)
+    }
+}
+
+#[cfg(unix)]
+impl From<UnixListener> for Listener {
+    fn from(listener: UnixListener) -> Self {
+        Listener::Unix {
+            listener,
+            path: None,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Listener::Unix {
+            path: Some(path), ..
+        } = self
+        {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// TCP 或 Unix 连接
+#[derive(Debug)]
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tcp_and_unix_addresses() {
+        assert_eq!(
+            "127.0.0.1:3000".parse::<ListenAddr>().unwrap(),
+            ListenAddr::Tcp("127.0.0.1:3000".parse().unwrap())
+        );
+        assert_eq!(
+            "[::1]:3000".parse::<ListenAddr>().unwrap(),
+            ListenAddr::Tcp(SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, 3000)))
+        );
+        assert_eq!(
+            "unix:/tmp/panorama.sock".parse::<ListenAddr>().unwrap(),
+            ListenAddr::Unix(PathBuf::from("/tmp/panorama.sock"))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        for s in ["", "unix:", "localhost:3000", "127.0.0.1", "::1:3000"] {
+            assert!(s.parse::<ListenAddr>().is_err(), "{:?} should be rejected", s);
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for s in ["0.0.0.0:3000", "[::1]:8080", "unix:/run/panorama.sock"] {
+            assert_eq!(s.parse::<ListenAddr>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn deserializes_from_string() {
+        let addrs: Vec<ListenAddr> =
+            serde_json::from_str(r#"["127.0.0.1:3000", "unix:/tmp/a.sock"]"#).unwrap();
+        assert_eq!(addrs.len(), 2);
+        assert!(serde_json::from_str::<Vec<ListenAddr>>(r#"["nope"]"#).is_err());
+    }
+
+    #[test]
+    fn only_dead_listener_errors_are_fatal() {
+        use io::ErrorKind;
+        assert!(!is_fatal_accept_error(&ErrorKind::ConnectionAborted.into()));
+        assert!(!is_fatal_accept_error(&ErrorKind::ConnectionReset.into()));
+        assert!(is_fatal_accept_error(&ErrorKind::InvalidInput.into()));
+        #[cfg(unix)]
+        {
+            let os_error = io::Error::from_raw_os_error;
+            for errno in [libc::EMFILE, libc::ENFILE, libc::ENOBUFS, libc::ENOMEM] {
+                assert!(!is_fatal_accept_error(&os_error(errno)));
+            }
+            assert!(is_fatal_accept_error(&os_error(libc::EBADF)));
+        }
+    }
+
+    #[cfg(unix)]
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("panorama-listen-{}-{}", std::process::id(), name))
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn bind_refuses_to_remove_regular_file() {
+        let path = temp_path("file");
+        std::fs::write(&path, "keep me").unwrap();
+        let err = Listener::bind(&ListenAddr::Unix(path.clone()))
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("not a unix socket"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn bind_replaces_stale_socket_but_not_live_one() {
+        let path = temp_path("sock");
+        let addr = ListenAddr::Unix(path.clone());
+        // std 的监听器 drop 后留下没有进程监听的 socket 文件
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let listener = Listener::bind(&addr).await.unwrap();
+        let err = Listener::bind(&addr).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("in use"));
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,121 @@
+// Prometheus 指标：http 请求、web socket 连接与消息、sqlite 查询，由 /metrics 以文本格式导出
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::time::Duration;
+
+/// 本进程所有指标都注册在这里，不使用 prometheus 的默认 registry
+pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+pub static HTTP_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("http_requests_total", "HTTP requests handled"),
+        &["method", "route", "status"],
+    ))
+});
+
+pub static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new("http_request_duration_seconds", "HTTP request latency"),
+        &["method", "route"],
+    ))
+});
+
+pub static WS_CONNECTIONS_ACTIVE: Lazy<IntGauge> = Lazy::new(|| {
+    register(IntGauge::new(
+        "ws_connections_active",
+        "Open WebSocket connections",
+    ))
+});
+
+pub static WS_CONNECTIONS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    register(IntCounter::new(
+        "ws_connections_total",
+        "WebSocket connections accepted",
+    ))
+});
+
+pub static WS_LISTENERS_BOUND: Lazy<IntGauge> = Lazy::new(|| {
+    register(IntGauge::new(
+        "ws_listeners_bound",
+        "Listeners bound by the standalone WebSocket server",
+    ))
+});
+
+/// direction 为 in（收到）或 out（发出）
+pub static WS_MESSAGES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("ws_messages_total", "WebSocket messages"),
+        &["direction"],
+    ))
+});
+
+pub static DB_QUERY_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new("sqlite_query_duration_seconds", "SQLite query latency").buckets(
+            vec![0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0],
+        ),
+        &["op"],
+    ))
+});
+
+pub static DB_QUERY_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("sqlite_query_errors_total", "SQLite queries that failed"),
+        &["op"],
+    ))
+});
+
+// 指标名和标签都是常量，创建或注册失败属于编码错误
+fn register<M>(metric: prometheus::Result<M>) -> M
+where
+    M: prometheus::core::Collector + Clone + 'static,
+{
+    let metric = metric.expect("invalid metric definition");
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("metric registered twice");
+    metric
+}
+
+/// 按 Prometheus 文本格式导出所有指标
+pub fn gather() -> prometheus::Result<String> {
+    let mut buf = Vec::new();
+    TextEncoder::new().encode(&REGISTRY.gather(), &mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// 记录一次 sqlite 操作的耗时，失败时错误计数加一（查询无结果不算失败），返回是否失败。
+/// 由 sql_stats::observe 调用，计时和 SQL 统计共用同一个耗时。
+pub fn record_query<T>(op: &str, elapsed: Duration, result: &rusqlite::Result<T>) -> bool {
+    DB_QUERY_DURATION
+        .with_label_values(&[op])
+        .observe(elapsed.as_secs_f64());
+    let failed = matches!(result, Err(e) if !matches!(e, rusqlite::Error::QueryReturnedNoRows));
+    if failed {
+        DB_QUERY_ERRORS.with_label_values(&[op]).inc();
+    }
+    failed
+}
+
+/// 创建时 gauge 加 n，drop 时减 n，任务被中止也能正确计数
+pub struct GaugeGuard {
+    gauge: &'static IntGauge,
+    n: i64,
+}
+
+impl GaugeGuard {
+    pub fn add(gauge: &'static Lazy<IntGauge>, n: i64) -> Self {
+        let gauge: &'static IntGauge = gauge;
+        gauge.add(n);
+        Self { gauge, n }
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.gauge.sub(self.n);
+    }
+}
@@ -1,8 +1,9 @@
+pub mod clock;
+pub mod config;
+pub mod error;
 pub mod global;
-
-
-#[derive(Debug)]
-pub enum MyError {
-    MissingValue,
-    InvalidValue,
-}
\ No newline at end of file
+pub mod listen;
+pub mod metrics;
+pub mod password;
+pub mod secret;
+pub mod supervisor;
@@ -0,0 +1,51 @@
+// 密码哈希：argon2id，每个密码随机生成 salt，哈希以 PHC 字符串保存（包含算法参数和 salt）
+use crate::common::error::{AppError, AppResult};
+use crate::common::secret::Secret;
+use anyhow::anyhow;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use once_cell::sync::Lazy;
+
+pub const MIN_LEN: usize = 8;
+pub const MAX_LEN: usize = 128;
+
+// 用户不存在时也做一次同样代价的校验，避免通过响应时间判断用户名是否存在
+static DUMMY_HASH: Lazy<Secret> =
+    Lazy::new(|| hash(&Secret::new("dummy password for timing")).expect("hash dummy password"));
+
+pub fn validate(password: &Secret) -> AppResult<()> {
+    let len = password.expose().chars().count();
+    if !(MIN_LEN..=MAX_LEN).contains(&len) {
+        return Err(AppError::Validation(format!(
+            "password must be {} to {} characters",
+            MIN_LEN, MAX_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// 计算密码哈希，耗时几十毫秒，调用方应在阻塞线程池上执行
+pub fn hash(password: &Secret) -> AppResult<Secret> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.expose().as_bytes(), &salt)
+        .map_err(|e| AppError::Internal(anyhow!("hash password failed: {}", e)))?;
+    Ok(Secret::new(hash.to_string()))
+}
+
+/// 校验密码，比较是常量时间的。hash 为 None（用户不存在或没有设置密码）时总是返回 false。
+pub fn verify(password: &Secret, hash: Option<&Secret>) -> bool {
+    let (hash, known) = match hash {
+        Some(hash) => (hash.expose(), true),
+        None => (DUMMY_HASH.expose(), false),
+    };
+    let parsed = match PasswordHash::new(hash) {
+        Ok(parsed) => parsed,
+        Err(_) => return false,
+    };
+    let matched = Argon2::default()
+        .verify_password(password.expose().as_bytes(), &parsed)
+        .is_ok();
+    matched && known
+}
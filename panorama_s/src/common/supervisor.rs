@@ -0,0 +1,408 @@
+// 长期运行任务的监管：失败后按退避重启，记录状态，关键服务无法恢复时返回错误
+use crate::common::config::SupervisorSettings;
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use log::{error, info, warn};
+use panorama_utils::shutdown::ShutdownToken;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::{JoinError, JoinHandle, JoinSet};
+
+type ServiceFactory = Arc<dyn Fn(Ready) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceState {
+    Starting,
+    Running,
+    Restarting,
+    Stopped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub state: ServiceState,
+    pub critical: bool,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+}
+
+/// 所有服务的状态表，可以 clone 后在别处读取
+#[derive(Debug, Clone, Default)]
+pub struct ServiceRegistry {
+    inner: Arc<RwLock<BTreeMap<String, ServiceStatus>>>,
+}
+
+impl ServiceRegistry {
+    pub fn snapshot(&self) -> BTreeMap<String, ServiceStatus> {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut ServiceStatus)) {
+        let mut map = self
+            .inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(status) = map.get_mut(name) {
+            f(status);
+        }
+    }
+
+    fn set_state(&self, name: &str, state: ServiceState) {
+        info!("[supervisor] {} -> {:?}", name, state);
+        self.update(name, |s| s.state = state);
+    }
+}
+
+/// 交给服务的就绪通知。服务完成启动（例如监听地址已绑定）后调用 ready，
+/// 状态才从 starting 变为 running；启动失败的服务不会被误报为 running。
+#[derive(Debug, Clone)]
+pub struct Ready {
+    name: Arc<str>,
+    registry: ServiceRegistry,
+}
+
+impl Ready {
+    pub fn ready(&self) {
+        self.registry.set_state(&self.name, ServiceState::Running);
+    }
+}
+
+struct ServiceSpec {
+    name: String,
+    critical: bool,
+    factory: ServiceFactory,
+}
+
+pub struct Supervisor {
+    settings: SupervisorSettings,
+    shutdown: ShutdownToken,
+    services: Vec<ServiceSpec>,
+    registry: ServiceRegistry,
+}
+
+impl Supervisor {
+    /// shutdown 被 cancel 后不再重启任何服务
+    pub fn new(settings: SupervisorSettings, shutdown: ShutdownToken) -> Self {
+        Self {
+            settings,
+            shutdown,
+            services: Vec::new(),
+            registry: ServiceRegistry::default(),
+        }
+    }
+
+    /// 注册一个服务。factory 每次（重）启动时调用一次，启动完成后调用传入的 Ready::ready。
+    /// critical 为 true 的服务在关闭前结束（包括正常返回）都会重启，重启次数耗尽后 run 返回错误。
+    pub fn add<F, Fut>(&mut self, name: &str, critical: bool, factory: F)
+    where
+        F: Fn(Ready) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.registry
+            .inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(
+                name.to_string(),
+                ServiceStatus {
+                    state: ServiceState::Starting,
+                    critical,
+                    restarts: 0,
+                    last_error: None,
+                },
+            );
+        self.services.push(ServiceSpec {
+            name: name.to_string(),
+            critical,
+            factory: Arc::new(move |ready| Box::pin(factory(ready))),
+        });
+    }
+
+    pub fn registry(&self) -> ServiceRegistry {
+        self.registry.clone()
+    }
+
+    /// 启动所有服务并等待。
+    /// 所有服务正常结束时返回 Ok；任一关键服务无法恢复时 cancel shutdown 通知其余服务排空，
+    /// 等它们结束后返回该错误。
+    pub async fn run(self) -> Result<()> {
+        let mut set = JoinSet::new();
+        for spec in self.services {
+            let settings = self.settings.clone();
+            let shutdown = self.shutdown.clone();
+            let registry = self.registry.clone();
+            set.spawn(async move {
+                let result = supervise(&spec, &settings, &shutdown, &registry).await;
+                (spec.name, spec.critical, result)
+            });
+        }
+
+        let mut failed = None;
+        while let Some(joined) = set.join_next().await {
+            let (name, critical, result) = joined.map_err(|e| anyhow!("supervisor task: {}", e))?;
+            if let Err(e) = result {
+                if critical {
+                    error!(
+                        "[supervisor] critical service {} failed, shutting down",
+                        name
+                    );
+                    self.shutdown.cancel();
+                    failed.get_or_insert(e.context(format!("critical service {} failed", name)));
+                    continue;
+                }
+                warn!(
+                    "[supervisor] non-critical service {} gave up: {:#}",
+                    name, e
+                );
+            }
+        }
+        failed.map_or(Ok(()), Err)
+    }
+}
+
+async fn supervise(
+    spec: &ServiceSpec,
+    settings: &SupervisorSettings,
+    shutdown: &ShutdownToken,
+    registry: &ServiceRegistry,
+) -> Result<()> {
+    let reset_after = Duration::from_secs(settings.reset_after_secs);
+    let mut backoff = Duration::from_millis(settings.initial_backoff_ms);
+    let max_backoff = Duration::from_millis(settings.max_backoff_ms);
+    let mut attempts = 0;
+    let ready = Ready {
+        name: Arc::from(spec.name.as_str()),
+        registry: registry.clone(),
+    };
+
+    loop {
+        registry.set_state(&spec.name, ServiceState::Starting);
+        let started = Instant::now();
+
+        // 放到独立任务里运行，panic 也能被捕获；supervise 被 abort 时一起 abort
+        let handle = AbortOnDrop(tokio::spawn((spec.factory)(ready.clone())));
+        let err = match handle.join().await {
+            // 关键服务只应在关闭时结束，提前正常返回按失败处理并重启
+            Ok(Ok(())) if spec.critical && !shutdown.is_cancelled() => {
+                anyhow!("exited before shutdown")
+            }
+            Ok(Ok(())) => {
+                registry.set_state(&spec.name, ServiceState::Stopped);
+                return Ok(());
+            }
+            Ok(Err(e)) => e,
+            Err(e) => anyhow!("panicked: {}", e),
+        };
+        error!("[supervisor] {} failed: {:#}", spec.name, err);
+        registry.update(&spec.name, |s| s.last_error = Some(format!("{:#}", err)));
+
+        // 关闭过程中不再重启
+        if shutdown.is_cancelled() {
+            registry.set_state(&spec.name, ServiceState::Stopped);
+            return Ok(());
+        }
+
+        // 稳定运行一段时间后重新计数
+        if started.elapsed() >= reset_after {
+            attempts = 0;
+            backoff = Duration::from_millis(settings.initial_backoff_ms);
+        }
+        if attempts >= settings.max_restarts {
+            registry.set_state(&spec.name, ServiceState::Failed);
+            return Err(err.context(format!("gave up after {} restarts", attempts)));
+        }
+
+        attempts += 1;
+        registry.update(&spec.name, |s| s.restarts += 1);
+        registry.set_state(&spec.name, ServiceState::Restarting);
+        warn!(
+            "[supervisor] restarting {} in {:?} (attempt {}/{})",
+            spec.name, backoff, attempts, settings.max_restarts
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = shutdown.cancelled() => {
+                registry.set_state(&spec.name, ServiceState::Stopped);
+                return Ok(());
+            }
+        }
+        backoff = (backoff * 2).min(max_backoff);
+    }
+}
+
+// drop 时 abort 任务，避免 supervise 被 abort 后服务任务变成无人管理的后台任务
+struct AbortOnDrop(JoinHandle<Result<()>>);
+
+impl AbortOnDrop {
+    async fn join(mut self) -> Result<Result<()>, JoinError> {
+        (&mut self.0).await
+    }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn settings(max_restarts: u32) -> SupervisorSettings {
+        SupervisorSettings {
+            max_restarts,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 4,
+            reset_after_secs: 60,
+        }
+    }
+
+    #[tokio::test]
+    async fn restarts_failed_service_until_it_succeeds() {
+        let mut supervisor = Supervisor::new(settings(5), ShutdownToken::new());
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        supervisor.add("flaky", false, move |ready| {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                ready.ready();
+                if attempt < 2 {
+                    Err(anyhow!("attempt {} failed", attempt))
+                } else {
+                    Ok(())
+                }
+            }
+        });
+        let registry = supervisor.registry();
+
+        supervisor.run().await.unwrap();
+        let status = &registry.snapshot()["flaky"];
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(status.restarts, 2);
+        assert_eq!(status.state, ServiceState::Stopped);
+        assert_eq!(status.last_error.as_deref(), Some("attempt 1 failed"));
+    }
+
+    #[tokio::test]
+    async fn backoff_doubles_up_to_the_limit_then_gives_up() {
+        let settings = SupervisorSettings {
+            max_restarts: 3,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 20,
+            reset_after_secs: 60,
+        };
+        let mut supervisor = Supervisor::new(settings, ShutdownToken::new());
+        supervisor.add("optional", false, |_| async { Err(anyhow!("down")) });
+        supervisor.add("panics", false, |_| async { panic!("bug") });
+        let registry = supervisor.registry();
+
+        // 非关键服务放弃重启不影响 run 的结果；三次等待为 10 + 20 + 20 毫秒
+        let started = Instant::now();
+        supervisor.run().await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        let services = registry.snapshot();
+        for name in ["optional", "panics"] {
+            assert_eq!(services[name].state, ServiceState::Failed);
+            assert_eq!(services[name].restarts, 3);
+        }
+        assert!(services["panics"]
+            .last_error
+            .as_deref()
+            .unwrap()
+            .contains("panicked"));
+    }
+
+    #[tokio::test]
+    async fn critical_service_exiting_early_is_restarted() {
+        let mut supervisor = Supervisor::new(settings(2), ShutdownToken::new());
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        supervisor.add("server", true, move |ready| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                ready.ready();
+                Ok(())
+            }
+        });
+        let registry = supervisor.registry();
+
+        let err = supervisor.run().await.unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("exited before shutdown"),
+            "{:#}",
+            err
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let status = &registry.snapshot()["server"];
+        assert_eq!(status.state, ServiceState::Failed);
+        assert_eq!(status.restarts, 2);
+    }
+
+    #[tokio::test]
+    async fn critical_failure_cancels_shutdown_and_waits_for_others() {
+        let shutdown = ShutdownToken::new();
+        let mut supervisor = Supervisor::new(settings(1), shutdown.clone());
+        supervisor.add("broken", true, |_| async { Err(anyhow!("boom")) });
+        let token = shutdown.clone();
+        supervisor.add("worker", false, move |ready| {
+            let token = token.clone();
+            async move {
+                ready.ready();
+                token.cancelled().await;
+                Ok(())
+            }
+        });
+        let registry = supervisor.registry();
+
+        let err = supervisor.run().await.unwrap_err();
+        assert!(format!("{:#}", err).contains("critical service broken failed"));
+        assert!(shutdown.is_cancelled());
+        let services = registry.snapshot();
+        assert_eq!(services["broken"].state, ServiceState::Failed);
+        assert_eq!(services["broken"].restarts, 1);
+        assert_eq!(services["worker"].state, ServiceState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn service_is_starting_until_ready() {
+        let shutdown = ShutdownToken::new();
+        let mut supervisor = Supervisor::new(settings(0), shutdown.clone());
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let rx = Arc::new(tokio::sync::Mutex::new(Some(rx)));
+        let token = shutdown.clone();
+        supervisor.add("slow", true, move |ready| {
+            let rx = rx.clone();
+            let token = token.clone();
+            async move {
+                if let Some(rx) = rx.lock().await.take() {
+                    let _ = rx.await;
+                }
+                ready.ready();
+                token.cancelled().await;
+                Ok(())
+            }
+        });
+        let registry = supervisor.registry();
+        let running = tokio::spawn(supervisor.run());
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(registry.snapshot()["slow"].state, ServiceState::Starting);
+        tx.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(registry.snapshot()["slow"].state, ServiceState::Running);
+
+        shutdown.cancel();
+        running.await.unwrap().unwrap();
+        assert_eq!(registry.snapshot()["slow"].state, ServiceState::Stopped);
+    }
+}
@@ -0,0 +1,9 @@
+// panorama_s 的库部分：main.rs 和 tests/ 下的集成测试都通过 panorama_s:: 使用这些模块
+pub mod common;
+// 语法练习的示例代码，保留了没有调用的示例
+#[allow(unused)]
+pub mod rust_lang;
+pub mod sqlite_sample;
+pub mod use_sqlite;
+pub mod web_server;
+pub mod web_socket;
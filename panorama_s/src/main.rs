@@ -1,65 +1,151 @@
-#![allow(unused)] // 全局屏蔽 unused 警告
-mod common;
-mod rust_lang;
-mod sqlite_sample;
-mod use_sqlite;
-mod web_server;
-mod web_socket;
-
-use crate::common::global;
-// use crate::rust_lang;
-use crate::use_sqlite::use_sqlite;
-use crate::web_server::web_server_main;
-use crate::web_socket::ws_server;
-use anyhow::Result;
-use log::{error, info};
-use log4rs;
-use rust_utils::graceful_shutdown;
+mod cli;
+
+use crate::cli::{BackupCommand, Cli, Command};
+use panorama_s::common::config::Settings;
+use panorama_s::common::global;
+use panorama_s::common::supervisor::Supervisor;
+use panorama_s::rust_lang;
+use panorama_s::sqlite_sample::backup;
+use panorama_s::sqlite_sample::changes;
+use panorama_s::sqlite_sample::kv_store;
+use panorama_s::sqlite_sample::migrate;
+use panorama_s::sqlite_sample::sqlite_c::SqliteCrud;
+use panorama_s::use_sqlite::use_sqlite;
+use panorama_s::web_server::web_server_main;
+use panorama_s::web_socket::ws_server;
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use log::{error, info, warn};
+use panorama_utils::logging;
+use panorama_utils::shutdown::{graceful_shutdown, ShutdownReason, ShutdownToken};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinError;
 
 #[tokio::main]
 async fn main() {
-    println!("Hello, world!");
+    let cli = Cli::parse();
+
+    // 加载配置，命令行参数优先
+    let mut settings = match Settings::load(cli.config.as_deref()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("load settings Error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(level) = cli.log_level {
+        settings.log.level = Some(level);
+    }
+    let log_settings = settings.log.clone();
+    if let Err(e) = global::init_settings(settings) {
+        eprintln!("init settings Error: {}", e);
+    }
 
     // 初始化日志系统
-    if let Err(e) = log4rs::init_file(global::LOG4RS_YAML_PATH, Default::default()) {
-        eprintln!("init log4rs Error: {}", e);
+    match logging::init_logging(&log_settings.config_path, log_settings.level) {
+        Ok(control) => {
+            if let Err(e) = global::init_log_control(control) {
+                eprintln!("init log control Error: {}", e);
+            }
+        }
+        Err(e) => eprintln!("init log4rs Error: {:#}", e),
     }
     info!("init log4rs ok.");
 
+    let result = match cli.command.unwrap_or(Command::Serve { restore: None }) {
+        Command::Serve { restore } => serve(restore).await,
+        Command::Migrate => open_db().and_then(|db| cli::run_migrate(&db)),
+        // 备份不执行迁移，保留数据库原样
+        Command::Backup { command } => match command {
+            BackupCommand::Create => open_db().and_then(|db| cli::create_backup(&db)),
+            BackupCommand::List => cli::list_backups(),
+        },
+        Command::Kv { command } => init().and_then(|db| cli::run_kv(&db, command)),
+        Command::Users { command } => init().and_then(|db| cli::run_users(db, command)),
+    };
+    if let Err(e) = result {
+        error!("[main] failed: {:#}", e);
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn serve(restore: Option<PathBuf>) -> Result<()> {
+    println!("Hello, world!");
+
+    if let Some(path) = restore {
+        info!(">>> restore");
+        let settings = global::settings();
+        backup::restore(&settings.sqlite, &settings.backup, &path).context("restore failed")?;
+    }
+
      info!("");
    info!("");
     info!(">>> init");
-    match init() {
-        Ok(_) => info!("[init] ok."),
-        Err(e) => error!("[init] failed: {}", e),
-    }
+    // 数据库打不开或 schema 版本不对时拒绝启动
+    let db = init().context("init failed")?;
+    info!("[init] ok.");
 
      info!("");
    info!("");
     info!(">>> use sqlite");
-    match use_sqlite() {
+    match use_sqlite(&db) {
         Ok(_) => info!("[sqlite] use_sqlite ok."),
         Err(e) => error!("[sqlite] use_sqlite failed: {}", e),
     }
 
-     info!("");
-   info!("");
-    info!(">>> start web server");
-    // 启动 Web 服务器（后台运行）
-    let _ = tokio::spawn(async {
-        if let Err(e) = web_server_main::run_server().await {
-            error!("web_server_main error: {}", e);
-        }
-    });
-
-   info!("");
-   info!("");
-    info!(">>> websocket listen");
-    let _ = tokio::spawn(async {
-        if let Err(e) = ws_server::run_server().await {
-            error!("web_server error: {}", e);
-        }
-    });
+    info!("");
+    info!("");
+    info!(">>> start services");
+    // web server、websocket server 和过期 key 清理交给 supervisor 管理，失败后自动重启
+    let shutdown = ShutdownToken::new();
+    let mut supervisor = Supervisor::new(global::settings().supervisor.clone(), shutdown.clone());
+    {
+        // http 处理器通过 Extension 使用同一个数据库
+        let token = shutdown.clone();
+        let db = db.clone();
+        supervisor.add("web_server", true, move |ready| {
+            web_server_main::run_server(db.clone(), token.clone(), ready)
+        });
+    }
+    if global::settings().ws_server.enabled {
+        let token = shutdown.clone();
+        supervisor.add("ws_server", true, move |ready| {
+            ws_server::run_server(token.clone(), ready)
+        });
+    }
+    let expiry_interval = global::settings().kv.expiry_interval_secs;
+    if expiry_interval > 0 {
+        let token = shutdown.clone();
+        let db = db.clone();
+        let interval = Duration::from_secs(expiry_interval);
+        supervisor.add("kv_expiry", false, move |ready| {
+            kv_store::run_expiry(db.clone(), interval, token.clone(), ready)
+        });
+    }
+    let changes_settings = global::settings().changes.clone();
+    if changes_settings.poll_interval_ms > 0 {
+        let token = shutdown.clone();
+        let db = db.clone();
+        supervisor.add("change_feed", false, move |ready| {
+            changes::run_feed(db.clone(), changes_settings.clone(), token.clone(), ready)
+        });
+    }
+    // 触发器总会记录变更，推送关闭时也要清理
+    {
+        let token = shutdown.clone();
+        let db = db.clone();
+        let retention = Duration::from_secs(global::settings().changes.retention_secs);
+        supervisor.add("changes_prune", false, move |ready| {
+            changes::run_prune(db.clone(), retention, token.clone(), ready)
+        });
+    }
+    if let Err(e) = global::init_services(supervisor.registry()) {
+        error!("init services failed: {}", e);
+    }
+    let mut services = tokio::spawn(supervisor.run());
 
     info!("");
    info!("");
@@ -72,16 +158,71 @@ async fn main() {
     rust_lang::life_time::use_life_time();
 
 
-     info!("");
-   info!("");
+    info!("");
+    info!("");
     info!(">>> wait for exist");
-    match graceful_shutdown().await {
-        Ok(()) => info!("Shutdown successful"),
-        Err(e) => error!("Shutdown failed: {}", e),
+    // 关键服务失败时 supervisor 会 cancel shutdown，同样走下面的排空流程
+    let reason = tokio::select! {
+        joined = &mut services => return flatten_services(joined),
+        res = graceful_shutdown(&shutdown) => match res {
+            Ok(reason) => {
+                info!("Shutdown successful ({})", reason);
+                Some(reason)
+            }
+            Err(e) => {
+                error!("Shutdown failed: {}", e);
+                None
+            }
+        },
+    };
+
+    // 通知所有服务停止接收新连接并排空，最多等待 drain_timeout_secs
+    shutdown.cancel();
+    let deadline = Duration::from_secs(global::settings().shutdown.drain_timeout_secs);
+    info!(">>> draining services (deadline {:?})", deadline);
+    match tokio::time::timeout(deadline, &mut services).await {
+        Ok(joined) => flatten_services(joined),
+        Err(_) => {
+            // abort supervisor 会连带 abort 它管理的所有服务任务
+            services.abort();
+            warn!("drain deadline {:?} exceeded, exit anyway", deadline);
+            match reason {
+                Some(ShutdownReason::Cancelled) => {
+                    Err(anyhow!("services cancelled the shutdown and did not stop in time"))
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+fn flatten_services(joined: Result<Result<()>, JoinError>) -> Result<()> {
+    match joined {
+        Ok(Ok(())) => {
+            info!("all services stopped");
+            Ok(())
+        }
+        Ok(Err(e)) => Err(e),
+        Err(e) => Err(anyhow!("supervisor panicked: {}", e)),
+    }
+}
+
+// 打开数据库并迁移到最新版本（或检查版本）
+fn init() -> Result<Arc<SqliteCrud>> {
+    let db = open_db()?;
+    if global::settings().sqlite.auto_migrate {
+        let applied = migrate::apply(&db)?;
+        info!(
+            "[init] schema version {}, {} migrations applied.",
+            migrate::latest_version(),
+            applied.len()
+        );
+    } else {
+        migrate::ensure_current(&db)?;
     }
+    Ok(db)
 }
 
-fn init() -> Result<()> {
-    global::init_global_db(global::SQLITE_DB_PATH)?;
-    Ok(())
+fn open_db() -> Result<Arc<SqliteCrud>> {
+    Ok(Arc::new(SqliteCrud::open(&global::settings().sqlite)?))
 }
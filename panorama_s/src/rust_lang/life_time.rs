@@ -33,6 +33,7 @@ impl <'a> ImportantExcept<'a> {
 // 规则 3：如果方法有多个输入生命周期参数，但其中一个是 &self 或 &mut self，那么 self 的生命周期会被赋给所有输出生命周期参数
 
 // 此处应用了规则1 和规则2，所以不需要再手动声明生命周期，编译器会根据规则自动识别生命周期
+#[allow(clippy::redundant_slicing)] // 示例代码，保留切片写法
 fn first_word(s:&str) -> &str{
     let bytes = s.bytes();
     for (i,item) in bytes.into_iter().enumerate() {
@@ -89,6 +90,7 @@ fn err_sample() {
     // }
 
     // 正确的做法：返回所有权而不是引用
+    #[allow(clippy::let_and_return)] // 示例代码，保留局部变量
     fn no_dangle() -> String {
         let s = String::from("hello");
         s // ✅ 正确：返回所有权
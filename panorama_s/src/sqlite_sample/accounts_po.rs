@@ -0,0 +1,91 @@
+use crate::common::error::{AppError, AppResult};
+use crate::sqlite_sample::repository::{Entity, FromRow, Repository, ToRow};
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use log::info;
+use rusqlite::{Row, ToSql};
+use serde::Serialize;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Account {
+    pub id: i64,
+    pub name: String,
+    /// 余额，单位为分
+    pub balance_cents: i64,
+}
+
+impl Account {
+    pub fn new(name: &str, balance_cents: i64) -> Self {
+        Self {
+            id: 0,
+            name: name.to_string(),
+            balance_cents,
+        }
+    }
+}
+
+impl FromRow for Account {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Account {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            balance_cents: row.get("balance_cents")?,
+        })
+    }
+}
+
+impl ToRow for Account {
+    fn to_row(&self) -> Vec<&dyn ToSql> {
+        vec![&self.name, &self.balance_cents]
+    }
+}
+
+impl Entity for Account {
+    const TABLE: &'static str = "accounts";
+    const COLUMNS: &'static [&'static str] = &["name", "balance_cents"];
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+}
+
+/// 转账：两个账户的余额在同一个事务里修改，任何一步失败都整体回滚。
+/// 返回转账后的 (转出账户, 转入账户)。
+pub fn transfer(
+    db: &SqliteCrud,
+    from: i64,
+    to: i64,
+    amount_cents: i64,
+) -> AppResult<(Account, Account)> {
+    if amount_cents <= 0 {
+        return Err(AppError::Validation("amount must be positive".into()));
+    }
+    if from == to {
+        return Err(AppError::Validation(
+            "cannot transfer to the same account".into(),
+        ));
+    }
+
+    let result = db.transaction(|tx| {
+        let repo = Repository::<Account>::in_tx(tx);
+        let mut source = repo.get(from)?;
+        let mut target = repo.get(to)?;
+        if source.balance_cents < amount_cents {
+            return Err(AppError::Conflict(format!(
+                "insufficient balance in account {}",
+                from
+            )));
+        }
+
+        source.balance_cents -= amount_cents;
+        target.balance_cents += amount_cents;
+        repo.update(&source)?;
+        repo.update(&target)?;
+        Ok((source, target))
+    })?;
+
+    info!(
+        "[sqlite] transfer ok. from:{} to:{} amount:{}",
+        from, to, amount_cents
+    );
+    Ok(result)
+}
@@ -0,0 +1,388 @@
+// 在线备份与恢复：用 sqlite 的 backup API 从只读连接复制整个数据库，WAL 模式下不阻塞写入。
+// 备份文件名为 <数据库文件名>-<UTC 时间>.db，按文件名排序即按时间排序，只保留最近 keep 个。
+use crate::common::clock::{self, now_ms};
+use crate::common::config::{BackupSettings, SqliteSettings};
+use crate::common::error::{AppError, AppResult};
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use anyhow::{anyhow, Context};
+use log::{info, warn};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 源数据库被锁住时重试的次数和间隔
+const BUSY_RETRIES: u32 = 50;
+const BUSY_PAUSE: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Serialize)]
+pub struct BackupFile {
+    pub name: String,
+    pub path: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupReport {
+    pub backup: BackupFile,
+    pub duration_ms: u128,
+    /// 超出保留数量被删除的旧备份
+    pub removed: Vec<String>,
+}
+
+/// 备份当前数据库并清理旧备份
+pub fn create(db: &SqliteCrud, settings: &BackupSettings) -> AppResult<BackupReport> {
+    let started = Instant::now();
+    let dir = Path::new(&settings.dir);
+    fs::create_dir_all(dir)
+        .with_context(|| format!("create backup dir {} failed", dir.display()))?;
+
+    let prefix = backup_prefix(db.path());
+    let name = format!("{}{}.db", prefix, clock::format_compact(now_ms()));
+    let path = dir.join(&name);
+    {
+        let conn = db.read()?;
+        copy_to(&conn, &path)?;
+    }
+    let backup = describe(&path)?;
+    let removed = prune(dir, &prefix, settings.keep)?;
+
+    info!(
+        "[backup] created {} ({} bytes) in {:?}, removed {} old backups.",
+        backup.path,
+        backup.size_bytes,
+        started.elapsed(),
+        removed.len()
+    );
+    Ok(BackupReport {
+        backup,
+        duration_ms: started.elapsed().as_millis(),
+        removed,
+    })
+}
+
+/// 列出当前数据库的所有备份，最新的在前
+pub fn list(db_path: &str, settings: &BackupSettings) -> AppResult<Vec<BackupFile>> {
+    let mut names = backup_names(Path::new(&settings.dir), &backup_prefix(db_path))?;
+    names.reverse();
+    names
+        .iter()
+        .map(|name| describe(&Path::new(&settings.dir).join(name)))
+        .collect()
+}
+
+/// 启动时用备份替换数据库，必须在打开连接池之前调用。
+/// backup 可以是文件路径，也可以是备份目录中的文件名。替换前先把当前数据库再备份一次。
+pub fn restore(
+    sqlite: &SqliteSettings,
+    settings: &BackupSettings,
+    backup: &Path,
+) -> AppResult<PathBuf> {
+    let source = resolve(settings, backup)?;
+    let src = Connection::open_with_flags(&source, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("open backup {} failed", source.display()))?;
+    let check: String = src.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        return Err(AppError::Validation(format!(
+            "backup {} is corrupted: {}",
+            source.display(),
+            check
+        )));
+    }
+
+    let existed = Path::new(&sqlite.db_path).exists();
+    let mut dst = Connection::open(&sqlite.db_path)?;
+    dst.busy_timeout(Duration::from_millis(sqlite.busy_timeout_ms))?;
+    if existed {
+        let dir = Path::new(&settings.dir);
+        fs::create_dir_all(dir)
+            .with_context(|| format!("create backup dir {} failed", dir.display()))?;
+        let name = format!(
+            "{}{}.db",
+            backup_prefix(&sqlite.db_path),
+            clock::format_compact(now_ms())
+        );
+        copy_to(&dst, &dir.join(&name))?;
+        prune(dir, &backup_prefix(&sqlite.db_path), settings.keep)?;
+        info!(
+            "[backup] current database saved to {} before restore.",
+            name
+        );
+    }
+
+    run(&Backup::new(&src, &mut dst)?)?;
+    info!(
+        "[backup] restored {} from {}.",
+        sqlite.db_path,
+        source.display()
+    );
+    Ok(source)
+}
+
+// 先写到临时文件再改名，中途失败不会留下不完整的备份
+fn copy_to(conn: &Connection, path: &Path) -> AppResult<()> {
+    let tmp = path.with_extension("db.tmp");
+    let result: AppResult<()> = (|| {
+        let mut dst = Connection::open(&tmp)?;
+        run(&Backup::new(conn, &mut dst)?)?;
+        // 备份会带上源数据库的 WAL 标记，改回普通模式，单个文件即可完整复制、只读打开
+        dst.query_row("PRAGMA journal_mode=DELETE", [], |row| {
+            row.get::<_, String>(0)
+        })?;
+        dst.close().map_err(|(_, e)| e)?;
+        fs::rename(&tmp, path).with_context(|| format!("rename {} failed", tmp.display()))?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+// 一步复制所有页：源连接在同一个读事务里完成，WAL 模式下得到一致的快照
+fn run(backup: &Backup<'_, '_>) -> AppResult<()> {
+    for _ in 0..BUSY_RETRIES {
+        match backup.step(-1)? {
+            StepResult::Done => return Ok(()),
+            StepResult::Busy | StepResult::Locked => thread::sleep(BUSY_PAUSE),
+            _ => {}
+        }
+    }
+    Err(AppError::Unavailable(
+        "database stayed locked during backup".into(),
+    ))
+}
+
+// 绝对路径或存在的相对路径直接使用，否则在备份目录中查找
+fn resolve(settings: &BackupSettings, backup: &Path) -> AppResult<PathBuf> {
+    if backup.exists() {
+        return Ok(backup.to_path_buf());
+    }
+    let in_dir = Path::new(&settings.dir).join(backup);
+    if backup.is_relative() && in_dir.exists() {
+        return Ok(in_dir);
+    }
+    Err(AppError::NotFound(format!(
+        "backup {} not found",
+        backup.display()
+    )))
+}
+
+// 按文件名保留最新的 keep 个，返回删除的文件名
+fn prune(dir: &Path, prefix: &str, keep: usize) -> AppResult<Vec<String>> {
+    if keep == 0 {
+        return Ok(Vec::new());
+    }
+    let names = backup_names(dir, prefix)?;
+    let excess = names.len().saturating_sub(keep);
+    let mut removed = Vec::with_capacity(excess);
+    for name in names.into_iter().take(excess) {
+        match fs::remove_file(dir.join(&name)) {
+            Ok(()) => removed.push(name),
+            Err(e) => warn!("[backup] remove old backup {} failed: {}", name, e),
+        }
+    }
+    Ok(removed)
+}
+
+// 目录中属于该数据库的备份文件名，从旧到新
+fn backup_names(dir: &Path, prefix: &str) -> AppResult<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(AppError::Internal(anyhow!(
+                "read backup dir {} failed: {}",
+                dir.display(),
+                e
+            )))
+        }
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| is_backup_name(name, prefix))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+// <prefix><UTC 时间>.db，时间部分必须是 format_compact 的格式，
+// 避免把 a-b.db 的备份当成 a.db 的备份（前缀都是 a-）
+fn is_backup_name(name: &str, prefix: &str) -> bool {
+    let Some(stamp) = name
+        .strip_prefix(prefix)
+        .and_then(|rest| rest.strip_suffix(".db"))
+    else {
+        return false;
+    };
+    let parts: Vec<&str> = stamp.split('-').collect();
+    matches!(parts.as_slice(), [date, time, millis]
+        if [(date, 8), (time, 6), (millis, 3)]
+            .iter()
+            .all(|(part, len)| part.len() == *len && part.bytes().all(|b| b.is_ascii_digit())))
+}
+
+fn describe(path: &Path) -> AppResult<BackupFile> {
+    let meta = fs::metadata(path).with_context(|| format!("stat {} failed", path.display()))?;
+    Ok(BackupFile {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        path: path.display().to_string(),
+        size_bytes: meta.len(),
+    })
+}
+
+// sqlite_sample.db -> "sqlite_sample-"
+fn backup_prefix(db_path: &str) -> String {
+    let stem = Path::new(db_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "db".to_string());
+    format!("{}-", stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 每个测试独立的临时目录，结束时删除
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "panorama-backup-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn touch(&self, names: &[&str]) {
+            for name in names {
+                fs::write(self.0.join(name), "").unwrap();
+            }
+        }
+
+        fn names(&self) -> Vec<String> {
+            let mut names: Vec<String> = fs::read_dir(&self.0)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn backup_prefix_uses_file_stem() {
+        assert_eq!(backup_prefix("src/sqlite_sample/sqlite_sample.db"), "sqlite_sample-");
+        assert_eq!(backup_prefix("data"), "data-");
+        assert_eq!(backup_prefix(""), "db-");
+    }
+
+    #[test]
+    fn backup_names_only_match_this_database() {
+        assert!(is_backup_name("a-20261018-091234-567.db", "a-"));
+        for name in [
+            "a-b-20261018-091234-567.db",
+            "a-20261018-091234-567.db.tmp",
+            "a-20261018-091234.db",
+            "a-2026101x-091234-567.db",
+            "b-20261018-091234-567.db",
+        ] {
+            assert!(!is_backup_name(name, "a-"), "{} should not match", name);
+        }
+    }
+
+    #[test]
+    fn prune_keeps_newest_backups() {
+        let dir = TempDir::new("prune");
+        dir.touch(&[
+            "a-20260101-000000-000.db",
+            "a-20260301-000000-000.db",
+            "a-20260201-000000-000.db",
+            "a-b-20250101-000000-000.db",
+            "a-20250101-000000-000.db.tmp",
+        ]);
+
+        assert!(prune(&dir.0, "a-", 0).unwrap().is_empty());
+        assert_eq!(
+            prune(&dir.0, "a-", 2).unwrap(),
+            vec!["a-20260101-000000-000.db"]
+        );
+        assert_eq!(
+            dir.names(),
+            vec![
+                "a-20250101-000000-000.db.tmp",
+                "a-20260201-000000-000.db",
+                "a-20260301-000000-000.db",
+                "a-b-20250101-000000-000.db",
+            ]
+        );
+        assert!(prune(&dir.0, "a-", 2).unwrap().is_empty());
+        assert!(prune(&dir.0.join("missing"), "a-", 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn create_list_and_restore() {
+        let dir = TempDir::new("restore");
+        let sqlite = SqliteSettings {
+            db_path: dir.0.join("app.db").display().to_string(),
+            ..SqliteSettings::default()
+        };
+        let settings = BackupSettings {
+            dir: dir.0.join("backups").display().to_string(),
+            keep: 5,
+        };
+        let count = |db: &SqliteCrud| -> i64 {
+            db.read()
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        let backup_name = {
+            let db = SqliteCrud::open(&sqlite).unwrap();
+            db.write()
+                .unwrap()
+                .execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1);")
+                .unwrap();
+            let report = create(&db, &settings).unwrap();
+            assert!(report.removed.is_empty());
+            db.write()
+                .unwrap()
+                .execute("INSERT INTO t VALUES (2)", [])
+                .unwrap();
+            assert_eq!(count(&db), 2);
+            report.backup.name
+        };
+        let listed = list(&sqlite.db_path, &settings).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, backup_name);
+
+        // 按备份目录中的文件名恢复，恢复前的数据库另存一份
+        restore(&sqlite, &settings, Path::new(&backup_name)).unwrap();
+        let db = SqliteCrud::open(&sqlite).unwrap();
+        assert_eq!(count(&db), 1);
+        assert_eq!(list(&sqlite.db_path, &settings).unwrap().len(), 2);
+        assert!(matches!(
+            restore(&sqlite, &settings, Path::new("missing.db")),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,215 @@
+// 变更通知：触发器把 table_test / users 的增删改写入 changes 表（见 0009_changes.sql），
+// change_feed 服务按 id 顺序读出新记录，通过广播通道发给订阅了的 web socket 连接；
+// changes_prune 服务定期删除过期记录，推送关闭时也运行，changes 表不会无限增长。
+use crate::common::clock::now_ms;
+use crate::common::config::ChangesSettings;
+use crate::common::error::AppResult;
+use crate::common::supervisor::Ready;
+use crate::sqlite_sample::sql_stats;
+use crate::sqlite_sample::sqlite_async;
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use anyhow::Result;
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use panorama_utils::shutdown::ShutdownToken;
+use rusqlite::params;
+use serde::Serialize;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// 可以订阅的表：kv 对应 table_test
+pub const TABLES: &[&str] = &["kv", "users"];
+
+// 一次最多读出的记录数，读满时不等待直接读下一批
+const BATCH: usize = 500;
+// 订阅者处理不过来时最多积压的事件数，超出后该订阅者收到 lagged
+const CHANNEL_CAPACITY: usize = 1024;
+// 清理过期记录的间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+static FEED: Lazy<broadcast::Sender<Arc<ChangeEvent>>> =
+    Lazy::new(|| broadcast::channel(CHANNEL_CAPACITY).0);
+// 最后广播的记录 id，change_feed 被 supervisor 重启后从这里继续，0 表示本进程还没有广播过
+static LAST_BROADCAST: AtomicI64 = AtomicI64::new(0);
+
+/// changes 表中的一条记录
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeEvent {
+    /// changes.id，单调递增
+    pub id: i64,
+    /// kv 或 users
+    pub table: String,
+    /// insert / update / delete
+    pub op: String,
+    /// table_test.id 或 users.id
+    pub row_id: i64,
+    /// kv 的 key 或用户的 username
+    pub key: String,
+    /// 修改时间（unix 毫秒）
+    pub at: i64,
+}
+
+/// 订阅之后读到的所有变更
+pub fn subscribe() -> broadcast::Receiver<Arc<ChangeEvent>> {
+    FEED.subscribe()
+}
+
+/// 当前最新的记录 id，没有记录时为 0
+pub fn latest_id(db: &SqliteCrud) -> AppResult<i64> {
+    let conn = db.read()?;
+    let id = sql_stats::observe(
+        &conn,
+        "changes_latest",
+        "SELECT COALESCE(MAX(id), 0) FROM changes",
+        |stmt| stmt.query_row([], |row| row.get(0)),
+    )?;
+    Ok(id)
+}
+
+/// id 大于 after 的记录，按 id 排序，最多 limit 条
+pub fn fetch_after(db: &SqliteCrud, after: i64, limit: usize) -> AppResult<Vec<ChangeEvent>> {
+    let conn = db.read()?;
+    let sql = "SELECT id, table_name, op, row_id, key, created_at FROM changes
+               WHERE id > ?1 ORDER BY id LIMIT ?2";
+    let events = sql_stats::observe(&conn, "changes_fetch", sql, |stmt| {
+        let rows = stmt.query_map(params![after, limit as i64], |row| {
+            Ok(ChangeEvent {
+                id: row.get(0)?,
+                table: row.get(1)?,
+                op: row.get(2)?,
+                row_id: row.get(3)?,
+                key: row.get(4)?,
+                at: row.get(5)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    Ok(events)
+}
+
+/// 删除 before（unix 毫秒）之前的记录，返回删除的行数
+pub fn prune(db: &SqliteCrud, before: i64) -> AppResult<usize> {
+    let conn = db.write()?;
+    let pruned = sql_stats::observe(
+        &conn,
+        "changes_prune",
+        "DELETE FROM changes WHERE created_at < ?1",
+        |stmt| stmt.execute([before]),
+    )?;
+    Ok(pruned)
+}
+
+/// 轮询 changes 表并广播新记录，收到关闭通知后退出。
+/// 被 supervisor 重启时从上次广播的记录之后继续；进程启动时从最新记录之后开始，
+/// 此时还没有任何订阅者，之前的变更不需要补发。
+pub async fn run_feed(
+    db: Arc<SqliteCrud>,
+    settings: ChangesSettings,
+    shutdown: ShutdownToken,
+    ready: Ready,
+) -> Result<()> {
+    let interval = Duration::from_millis(settings.poll_interval_ms);
+    let mut last_id = match LAST_BROADCAST.load(Ordering::Relaxed) {
+        0 => sqlite_async::with_handle(db.clone(), latest_id).await?,
+        id => id,
+    };
+    info!("[changes] change feed started after id {}.", last_id);
+    ready.ready();
+
+    let mut full = false;
+    loop {
+        if !full {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = shutdown.cancelled() => return Ok(()),
+            }
+        }
+        // 单次失败只记录日志，下一轮再试
+        let fetched =
+            sqlite_async::with_handle(db.clone(), move |db| fetch_after(db, last_id, BATCH)).await;
+        match fetched {
+            Ok(events) => {
+                full = events.len() == BATCH;
+                for event in events {
+                    last_id = event.id;
+                    // 没有订阅者时发送失败，直接丢弃
+                    let _ = FEED.send(Arc::new(event));
+                }
+                LAST_BROADCAST.store(last_id, Ordering::Relaxed);
+            }
+            Err(e) => {
+                full = false;
+                warn!("[changes] fetch changes failed: {:#}", e);
+            }
+        }
+    }
+}
+
+/// 每隔 PRUNE_INTERVAL 删除超过 retention 的记录，收到关闭通知后退出
+pub async fn run_prune(
+    db: Arc<SqliteCrud>,
+    retention: Duration,
+    shutdown: ShutdownToken,
+    ready: Ready,
+) -> Result<()> {
+    ready.ready();
+    loop {
+        let before =
+            now_ms().saturating_sub(i64::try_from(retention.as_millis()).unwrap_or(i64::MAX));
+        // 单次失败只记录日志，下一轮再试
+        match sqlite_async::with_handle(db.clone(), move |db| prune(db, before)).await {
+            Ok(0) => {}
+            Ok(pruned) => debug!("[changes] pruned {} old changes.", pruned),
+            Err(e) => warn!("[changes] prune changes failed: {:#}", e),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(PRUNE_INTERVAL) => {}
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite_sample::kv_store::KvStore;
+    use crate::sqlite_sample::migrate;
+
+    fn open_db() -> SqliteCrud {
+        let db = SqliteCrud::open_in_memory().unwrap();
+        migrate::apply(&db).unwrap();
+        db
+    }
+
+    #[test]
+    fn triggers_record_changes_in_order() {
+        let db = open_db();
+        let kv = KvStore::new(&db);
+        kv.set("a", "1", None).unwrap();
+        kv.set("a", "2", None).unwrap();
+        kv.delete("a").unwrap();
+
+        let events = fetch_after(&db, 0, BATCH).unwrap();
+        let ops: Vec<&str> = events.iter().map(|e| e.op.as_str()).collect();
+        assert_eq!(ops, ["insert", "update", "delete"]);
+        assert!(events.iter().all(|e| e.table == "kv" && e.key == "a"));
+        assert_eq!(latest_id(&db).unwrap(), events[2].id);
+        assert_eq!(
+            fetch_after(&db, events[0].id, 1).unwrap()[0].id,
+            events[1].id
+        );
+        assert!(fetch_after(&db, events[2].id, BATCH).unwrap().is_empty());
+    }
+
+    #[test]
+    fn prune_removes_only_older_records() {
+        let db = open_db();
+        KvStore::new(&db).set("a", "1", None).unwrap();
+        let at = fetch_after(&db, 0, BATCH).unwrap()[0].at;
+        assert_eq!(prune(&db, at).unwrap(), 0);
+        assert_eq!(prune(&db, at + 1).unwrap(), 1);
+        assert_eq!(latest_id(&db).unwrap(), 0);
+    }
+}
@@ -0,0 +1,54 @@
+// table_test 中的一个键值
+use crate::sqlite_sample::repository::{Entity, FromRow, ToRow};
+use rusqlite::{Row, ToSql};
+use serde::Serialize;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct KvEntry {
+    pub id: i64,
+    pub key: String,
+    pub value: Option<String>,
+    /// 每次写入加一，compare-and-swap 时比较
+    pub version: i64,
+    /// 过期时间（unix 毫秒），None 表示不过期
+    pub expires_at: Option<i64>,
+}
+
+impl KvEntry {
+    pub fn new(key: &str, value: &str) -> Self {
+        Self {
+            id: 0,
+            key: key.to_string(),
+            value: Some(value.to_string()),
+            version: 1,
+            expires_at: None,
+        }
+    }
+}
+
+impl FromRow for KvEntry {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(KvEntry {
+            id: row.get("id")?,
+            key: row.get("key")?,
+            value: row.get("value")?,
+            version: row.get("version")?,
+            expires_at: row.get("expires_at")?,
+        })
+    }
+}
+
+impl ToRow for KvEntry {
+    fn to_row(&self) -> Vec<&dyn ToSql> {
+        vec![&self.key, &self.value, &self.version, &self.expires_at]
+    }
+}
+
+impl Entity for KvEntry {
+    const TABLE: &'static str = "table_test";
+    const COLUMNS: &'static [&'static str] = &["key", "value", "version", "expires_at"];
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+}
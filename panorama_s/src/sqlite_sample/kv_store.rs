@@ -0,0 +1,340 @@
+// 键值存储：table_test 上的原子 upsert、前缀扫描、批量读写、按 key 过期和基于版本号的 compare-and-swap。
+// 过期的行对所有读写都视为不存在，由 kv_expiry 服务定期清理。
+use crate::common::clock::now_ms;
+use crate::common::error::{AppError, AppResult};
+use crate::common::supervisor::Ready;
+use crate::sqlite_sample::kv_po::KvEntry;
+use crate::sqlite_sample::repository::FromRow;
+use crate::sqlite_sample::sql_stats;
+use crate::sqlite_sample::sqlite_async;
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use anyhow::Result;
+use log::{info, warn};
+use panorama_utils::shutdown::ShutdownToken;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// scan 一次最多返回的行数
+pub const MAX_SCAN_LIMIT: usize = 1000;
+
+const SELECT: &str = "SELECT id, key, value, version, expires_at FROM table_test";
+// 未设置过期时间，或者还没到过期时间
+const LIVE: &str = "(expires_at IS NULL OR expires_at > ?2)";
+
+/// 批量写入中的一项
+#[derive(Debug, Clone)]
+pub struct KvWrite {
+    pub key: String,
+    pub value: String,
+    pub ttl: Option<Duration>,
+}
+
+pub struct KvStore<'a> {
+    db: &'a SqliteCrud,
+}
+
+impl<'a> KvStore<'a> {
+    pub fn new(db: &'a SqliteCrud) -> Self {
+        Self { db }
+    }
+
+    /// 读取一个未过期的 key，不存在时返回 NotFound
+    pub fn get(&self, key: &str) -> AppResult<KvEntry> {
+        let conn = self.db.read()?;
+        get_live(&conn, key, now_ms())?.ok_or_else(|| not_found(key))
+    }
+
+    /// 写入一个 key，已存在时覆盖并把版本号加一。ttl 为 None 时不过期。
+    pub fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> AppResult<KvEntry> {
+        check_key(key)?;
+        let expires_at = expires_at(ttl)?;
+        self.db
+            .transaction(|tx| Ok(upsert(tx, key, value, expires_at, now_ms())?))
+    }
+
+    /// 删除一个 key，返回删除前是否存在
+    pub fn delete(&self, key: &str) -> AppResult<bool> {
+        let conn = self.db.write()?;
+        let sql = format!("DELETE FROM table_test WHERE key = ?1 AND {}", LIVE);
+        let deleted = sql_stats::observe(&conn, "kv_delete", &sql, |stmt| {
+            stmt.execute(params![key, now_ms()])
+        })?;
+        Ok(deleted > 0)
+    }
+
+    /// 按 key 顺序列出以 prefix 开头的 key，最多 limit 个
+    pub fn scan(&self, prefix: &str, limit: usize) -> AppResult<Vec<KvEntry>> {
+        let limit = limit.clamp(1, MAX_SCAN_LIMIT) as i64;
+        // key >= prefix 可以走唯一索引，substr 比较排除 LIKE 通配符的影响
+        let sql = format!(
+            "{} WHERE key >= ?1 AND substr(key, 1, length(?1)) = ?1 AND {} ORDER BY key LIMIT ?3",
+            SELECT, LIVE
+        );
+        let conn = self.db.read()?;
+        let rows = sql_stats::observe(&conn, "kv_scan", &sql, |stmt| {
+            let rows = stmt.query_map(params![prefix, now_ms(), limit], KvEntry::from_row)?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+        })?;
+        Ok(rows)
+    }
+
+    /// 批量读取，只返回存在且未过期的 key，顺序与 keys 一致
+    pub fn get_many(&self, keys: &[String]) -> AppResult<Vec<KvEntry>> {
+        let now = now_ms();
+        let conn = self.db.read()?;
+        let mut found = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(entry) = get_live(&conn, key, now)? {
+                found.push(entry);
+            }
+        }
+        Ok(found)
+    }
+
+    /// 批量写入，全部在一个事务里，任一项失败时整体回滚
+    pub fn set_many(&self, items: &[KvWrite]) -> AppResult<Vec<KvEntry>> {
+        let mut expiries = Vec::with_capacity(items.len());
+        for item in items {
+            check_key(&item.key)?;
+            expiries.push(expires_at(item.ttl)?);
+        }
+        let now = now_ms();
+        self.db.transaction(|tx| {
+            let mut written = Vec::with_capacity(items.len());
+            for (item, expires_at) in items.iter().zip(expiries) {
+                written.push(upsert(tx, &item.key, &item.value, expires_at, now)?);
+            }
+            Ok(written)
+        })
+    }
+
+    /// expected_version 与当前版本一致时才写入，0 表示 key 必须不存在。
+    /// 版本不一致时返回 Conflict。
+    pub fn compare_and_swap(
+        &self,
+        key: &str,
+        expected_version: i64,
+        value: &str,
+        ttl: Option<Duration>,
+    ) -> AppResult<KvEntry> {
+        check_key(key)?;
+        let expires_at = expires_at(ttl)?;
+        let now = now_ms();
+        self.db.transaction(|tx| {
+            let current = get_live(tx, key, now)?.map_or(0, |e| e.version);
+            if current != expected_version {
+                return Err(AppError::Conflict(format!(
+                    "version mismatch for key {}: expected {}, current {}",
+                    key, expected_version, current
+                )));
+            }
+            Ok(upsert(tx, key, value, expires_at, now)?)
+        })
+    }
+
+    /// 删除所有已过期的行，返回删除的行数
+    pub fn purge_expired(&self) -> AppResult<usize> {
+        let conn = self.db.write()?;
+        let purged = sql_stats::observe(
+            &conn,
+            "kv_purge",
+            "DELETE FROM table_test WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+            |stmt| stmt.execute([now_ms()]),
+        )?;
+        Ok(purged)
+    }
+}
+
+/// 定期清理过期的 key，收到关闭通知后退出
+pub async fn run_expiry(
+    db: Arc<SqliteCrud>,
+    interval: Duration,
+    shutdown: ShutdownToken,
+    ready: Ready,
+) -> Result<()> {
+    ready.ready();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+        // 单次失败只记录日志，下一轮再试
+        match sqlite_async::with_handle(db.clone(), |db| KvStore::new(db).purge_expired()).await {
+            Ok(0) => {}
+            Ok(purged) => info!("[kv] purged {} expired keys.", purged),
+            Err(e) => warn!("[kv] purge expired keys failed: {:#}", e),
+        }
+    }
+}
+
+// 写入后读回，返回最新的版本号和过期时间。
+// key 已过期时视为新建，版本号从 1 开始。
+fn upsert(
+    conn: &Connection,
+    key: &str,
+    value: &str,
+    expires_at: Option<i64>,
+    now: i64,
+) -> rusqlite::Result<KvEntry> {
+    sql_stats::observe(
+        conn,
+        "kv_set",
+        "INSERT INTO table_test (key, value, version, expires_at) VALUES (?1, ?2, 1, ?3)
+         ON CONFLICT(key) DO UPDATE SET
+            value = excluded.value,
+            expires_at = excluded.expires_at,
+            version = CASE WHEN expires_at IS NOT NULL AND expires_at <= ?4
+                           THEN 1 ELSE version + 1 END",
+        |stmt| stmt.execute(params![key, value, expires_at, now]),
+    )?;
+    sql_stats::observe(conn, "kv_set_read", &format!("{} WHERE key = ?1", SELECT), |stmt| {
+        stmt.query_row([key], KvEntry::from_row)
+    })
+}
+
+fn get_live(conn: &Connection, key: &str, now: i64) -> rusqlite::Result<Option<KvEntry>> {
+    let sql = format!("{} WHERE key = ?1 AND {}", SELECT, LIVE);
+    sql_stats::observe(conn, "kv_get", &sql, |stmt| {
+        stmt.query_row(params![key, now], KvEntry::from_row).optional()
+    })
+}
+
+fn check_key(key: &str) -> AppResult<()> {
+    if key.is_empty() {
+        return Err(AppError::Validation("key must not be empty".into()));
+    }
+    Ok(())
+}
+
+fn expires_at(ttl: Option<Duration>) -> AppResult<Option<i64>> {
+    match ttl {
+        None => Ok(None),
+        Some(ttl) if ttl.is_zero() => Err(AppError::Validation("ttl must be positive".into())),
+        Some(ttl) => i64::try_from(ttl.as_millis())
+            .ok()
+            .and_then(|ms| now_ms().checked_add(ms))
+            .map(Some)
+            .ok_or_else(|| AppError::Validation(format!("ttl {:?} is too large", ttl))),
+    }
+}
+
+fn not_found(key: &str) -> AppError {
+    AppError::NotFound(format!("key {} not found", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite_sample::migrate;
+
+    fn open_db() -> SqliteCrud {
+        let db = SqliteCrud::open_in_memory().unwrap();
+        migrate::apply(&db).unwrap();
+        db
+    }
+
+    #[test]
+    fn set_bumps_version() {
+        let db = open_db();
+        let kv = KvStore::new(&db);
+        assert_eq!(kv.set("k", "v1", None).unwrap().version, 1);
+        assert_eq!(kv.set("k", "v2", None).unwrap().version, 2);
+        let entry = kv.get("k").unwrap();
+        assert_eq!((entry.value.as_deref(), entry.version), (Some("v2"), 2));
+        assert!(matches!(kv.set("", "v", None), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn compare_and_swap_checks_version() {
+        let db = open_db();
+        let kv = KvStore::new(&db);
+        // 0 表示 key 必须不存在
+        assert_eq!(kv.compare_and_swap("k", 0, "v1", None).unwrap().version, 1);
+        assert!(matches!(
+            kv.compare_and_swap("k", 0, "v2", None),
+            Err(AppError::Conflict(_))
+        ));
+        assert!(matches!(
+            kv.compare_and_swap("k", 2, "v2", None),
+            Err(AppError::Conflict(_))
+        ));
+        assert_eq!(kv.compare_and_swap("k", 1, "v2", None).unwrap().version, 2);
+        assert_eq!(kv.get("k").unwrap().value.as_deref(), Some("v2"));
+    }
+
+    #[test]
+    fn expired_keys_are_gone_and_purged() {
+        let db = open_db();
+        let kv = KvStore::new(&db);
+        kv.set("short", "v", Some(Duration::from_millis(1))).unwrap();
+        kv.set("short", "v", Some(Duration::from_millis(1))).unwrap();
+        kv.set("long", "v", Some(Duration::from_secs(3600))).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert!(matches!(kv.get("short"), Err(AppError::NotFound(_))));
+        assert!(!kv.delete("short").unwrap());
+        let keys: Vec<String> = kv.scan("", 10).unwrap().into_iter().map(|e| e.key).collect();
+        assert_eq!(keys, vec!["long"]);
+        // 过期的 key 视为不存在：CAS 期望 0，重新写入后版本从 1 开始
+        assert_eq!(kv.compare_and_swap("short", 0, "v", None).unwrap().version, 1);
+
+        kv.set("gone", "v", Some(Duration::from_millis(1))).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(kv.purge_expired().unwrap(), 1);
+        assert_eq!(kv.purge_expired().unwrap(), 0);
+    }
+
+    #[test]
+    fn invalid_ttl_is_rejected() {
+        let db = open_db();
+        let kv = KvStore::new(&db);
+        for ttl in [
+            Duration::ZERO,
+            Duration::from_secs(u64::MAX),
+            Duration::from_millis(i64::MAX as u64),
+        ] {
+            assert!(
+                matches!(kv.set("k", "v", Some(ttl)), Err(AppError::Validation(_))),
+                "{:?} should be rejected",
+                ttl
+            );
+        }
+        assert!(matches!(kv.get("k"), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn scan_matches_prefix_literally() {
+        let db = open_db();
+        let kv = KvStore::new(&db);
+        for key in ["a%1", "a%2", "ab", "b"] {
+            kv.set(key, "v", None).unwrap();
+        }
+        let keys: Vec<String> = kv.scan("a%", 10).unwrap().into_iter().map(|e| e.key).collect();
+        assert_eq!(keys, vec!["a%1", "a%2"]);
+        assert_eq!(kv.scan("a", 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn set_many_is_all_or_nothing() {
+        let db = open_db();
+        let kv = KvStore::new(&db);
+        let write = |key: &str| KvWrite {
+            key: key.to_string(),
+            value: "v".to_string(),
+            ttl: None,
+        };
+        assert!(matches!(
+            kv.set_many(&[write("a"), write("")]),
+            Err(AppError::Validation(_))
+        ));
+        assert!(kv.get_many(&["a".to_string()]).unwrap().is_empty());
+
+        assert_eq!(kv.set_many(&[write("a"), write("b")]).unwrap().len(), 2);
+        let found = kv
+            .get_many(&["b".to_string(), "missing".to_string(), "a".to_string()])
+            .unwrap();
+        let keys: Vec<&str> = found.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["b", "a"]);
+    }
+}
@@ -0,0 +1,225 @@
+// 数据库迁移：按版本号顺序执行内嵌的 sql，每个迁移一个事务，已执行的版本记录在 schema_version 表
+use crate::common::error::{AppError, AppResult};
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use anyhow::anyhow;
+use log::info;
+use rusqlite::{Connection, OptionalExtension};
+
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// 所有迁移，版本号必须从 1 开始连续递增。已发布的迁移不要修改，新增时追加到末尾。
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_table_test",
+        sql: include_str!("migrations/0001_create_table_test.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_users",
+        sql: include_str!("migrations/0002_create_users.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "create_products",
+        sql: include_str!("migrations/0003_create_products.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "create_accounts",
+        sql: include_str!("migrations/0004_create_accounts.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "kv_store",
+        sql: include_str!("migrations/0005_kv_store.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "user_accounts",
+        sql: include_str!("migrations/0006_user_accounts.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "user_passwords",
+        sql: include_str!("migrations/0007_user_passwords.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "search",
+        sql: include_str!("migrations/0008_search.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "changes",
+        sql: include_str!("migrations/0009_changes.sql"),
+    },
+];
+
+/// 本程序支持的最新版本
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// 数据库当前版本，还没有 schema_version 表时为 0
+pub fn current_version(conn: &Connection) -> AppResult<u32> {
+    let exists: Option<i64> = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type='table' AND name='schema_version'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if exists.is_none() {
+        return Ok(0);
+    }
+    let version: Option<u32> =
+        conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+            row.get(0)
+        })?;
+    Ok(version.unwrap_or(0))
+}
+
+// 数据库比程序新时不能继续，旧程序不知道新结构该怎么用
+fn check_not_newer(current: u32) -> AppResult<()> {
+    let latest = latest_version();
+    if current > latest {
+        return Err(AppError::Internal(anyhow!(
+            "database schema version {} is newer than this binary supports ({}), upgrade panorama_s",
+            current,
+            latest
+        )));
+    }
+    Ok(())
+}
+
+/// 执行所有未执行的迁移，返回本次执行的迁移
+pub fn apply(db: &SqliteCrud) -> AppResult<Vec<&'static Migration>> {
+    let current = {
+        let conn = db.write()?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )",
+        )?;
+        current_version(&conn)?
+    };
+    check_not_newer(current)?;
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        // 迁移的 sql 和版本记录在同一个事务里，失败时整体回滚
+        db.transaction(|tx| {
+            tx.execute_batch(migration.sql).map_err(|e| {
+                AppError::Internal(anyhow!(
+                    "migration {} ({}) failed: {}",
+                    migration.version,
+                    migration.name,
+                    e
+                ))
+            })?;
+            tx.execute(
+                "INSERT INTO schema_version (version, name) VALUES (?1, ?2)",
+                rusqlite::params![migration.version, migration.name],
+            )?;
+            Ok(())
+        })?;
+        info!(
+            "[sqlite] migration {} {} applied.",
+            migration.version, migration.name
+        );
+        applied.push(migration);
+    }
+    Ok(applied)
+}
+
+/// 不执行迁移，只检查数据库版本是否与程序一致
+pub fn ensure_current(db: &SqliteCrud) -> AppResult<()> {
+    let conn = db.read()?;
+    let current = current_version(&conn)?;
+    check_not_newer(current)?;
+    if current < latest_version() {
+        return Err(AppError::Internal(anyhow!(
+            "database schema version {} is older than {}, run `panorama_s migrate` first",
+            current,
+            latest_version()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_version(db: &SqliteCrud, version: u32) {
+        db.write()
+            .unwrap()
+            .execute(
+                "INSERT INTO schema_version (version, name) VALUES (?1, 'test')",
+                [version],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn versions_start_at_one_and_are_contiguous() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, i + 1, "{}", migration.name);
+        }
+        assert_eq!(latest_version(), MIGRATIONS.len() as u32);
+    }
+
+    #[test]
+    fn apply_runs_pending_migrations_once() {
+        let db = SqliteCrud::open_in_memory().unwrap();
+        assert!(matches!(ensure_current(&db), Err(AppError::Internal(_))));
+        assert_eq!(current_version(&db.read().unwrap()).unwrap(), 0);
+
+        assert_eq!(apply(&db).unwrap().len(), MIGRATIONS.len());
+        assert_eq!(
+            current_version(&db.read().unwrap()).unwrap(),
+            latest_version()
+        );
+        assert!(apply(&db).unwrap().is_empty());
+        ensure_current(&db).unwrap();
+    }
+
+    #[test]
+    fn older_database_needs_migrate() {
+        let db = SqliteCrud::open_in_memory().unwrap();
+        apply(&db).unwrap();
+        db.write()
+            .unwrap()
+            .execute(
+                "DELETE FROM schema_version WHERE version = ?1",
+                [latest_version()],
+            )
+            .unwrap();
+        let err = ensure_current(&db).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("run `panorama_s migrate`"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn newer_database_is_rejected() {
+        let db = SqliteCrud::open_in_memory().unwrap();
+        apply(&db).unwrap();
+        set_version(&db, latest_version() + 1);
+        let err = ensure_current(&db).unwrap_err();
+        assert!(format!("{:#}", err).contains("is newer than"), "{:#}", err);
+        let Err(err) = apply(&db) else {
+            panic!("apply accepted a newer database");
+        };
+        assert!(format!("{:#}", err).contains("is newer than"), "{:#}", err);
+    }
+}
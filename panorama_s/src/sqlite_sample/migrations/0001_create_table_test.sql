@@ -0,0 +1,6 @@
+-- 键值示例表，IF NOT EXISTS 兼容引入迁移之前已经建好的数据库
+CREATE TABLE IF NOT EXISTS table_test (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key TEXT NOT NULL,
+    value TEXT NULL
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS products (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    price_cents INTEGER NOT NULL DEFAULT 0
+);
+
+-- 原来 /products 返回的示例数据
+INSERT INTO products (name, price_cents) VALUES ('Rust Book', 3999), ('Axum Guide', 1999);
@@ -0,0 +1,6 @@
+-- 余额以分为单位，CHECK 保证任何情况下都不会出现负数
+CREATE TABLE IF NOT EXISTS accounts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    balance_cents INTEGER NOT NULL DEFAULT 0 CHECK (balance_cents >= 0)
+);
@@ -0,0 +1,11 @@
+-- table_test 改为键值存储：key 唯一，version 用于 compare-and-swap，expires_at 为过期时间（unix 毫秒）
+
+-- 之前 insert_data 不是原子的，可能留下重复的 key，只保留最新的一行
+DELETE FROM table_test WHERE id NOT IN (SELECT MAX(id) FROM table_test GROUP BY key);
+
+ALTER TABLE table_test ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE table_test ADD COLUMN expires_at INTEGER NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_table_test_key ON table_test (key);
+CREATE INDEX IF NOT EXISTS idx_table_test_expires_at ON table_test (expires_at)
+    WHERE expires_at IS NOT NULL;
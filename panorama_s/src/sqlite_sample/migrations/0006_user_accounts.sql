@@ -0,0 +1,33 @@
+-- users 增加唯一的 username、email、状态和时间戳（unix 毫秒）。
+-- sqlite 的 ALTER TABLE 不能添加带 UNIQUE 的列，这里重建表。
+
+CREATE TABLE users_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL COLLATE NOCASE,
+    email TEXT NULL COLLATE NOCASE,
+    name TEXT NOT NULL,
+    age INTEGER NOT NULL,
+    status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'disabled')),
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+-- 已有用户以 name 作为 username，重名的加上 #id 区分（# 不是合法的 username 字符，不会和新用户冲突）
+INSERT INTO users_new (id, username, email, name, age, status, created_at, updated_at)
+SELECT
+    id,
+    CASE WHEN id = (SELECT MIN(u.id) FROM users u WHERE u.name = users.name COLLATE NOCASE)
+         THEN name ELSE name || '#' || id END,
+    NULL,
+    name,
+    age,
+    'active',
+    CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER),
+    CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
+
+CREATE UNIQUE INDEX idx_users_username ON users (username);
+CREATE UNIQUE INDEX idx_users_email ON users (email) WHERE email IS NOT NULL;
@@ -0,0 +1,2 @@
+-- 密码哈希（argon2 PHC 字符串），为空的用户不能登录
+ALTER TABLE users ADD COLUMN password_hash TEXT NULL;
@@ -0,0 +1,50 @@
+-- 全文索引：kv_fts 索引 table_test 的 key / value，users_fts 索引 users 的 username / name / email。
+-- 都是外部内容表（content=），只保存索引，内容从原表读取，由触发器保持同步。
+
+CREATE VIRTUAL TABLE kv_fts USING fts5(
+    key, value,
+    content='table_test', content_rowid='id',
+    tokenize='unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER kv_fts_insert AFTER INSERT ON table_test BEGIN
+    INSERT INTO kv_fts (rowid, key, value) VALUES (new.id, new.key, new.value);
+END;
+
+CREATE TRIGGER kv_fts_delete AFTER DELETE ON table_test BEGIN
+    INSERT INTO kv_fts (kv_fts, rowid, key, value) VALUES ('delete', old.id, old.key, old.value);
+END;
+
+-- upsert 的 DO UPDATE 也会触发 UPDATE 触发器
+CREATE TRIGGER kv_fts_update AFTER UPDATE OF key, value ON table_test BEGIN
+    INSERT INTO kv_fts (kv_fts, rowid, key, value) VALUES ('delete', old.id, old.key, old.value);
+    INSERT INTO kv_fts (rowid, key, value) VALUES (new.id, new.key, new.value);
+END;
+
+CREATE VIRTUAL TABLE users_fts USING fts5(
+    username, name, email,
+    content='users', content_rowid='id',
+    tokenize='unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER users_fts_insert AFTER INSERT ON users BEGIN
+    INSERT INTO users_fts (rowid, username, name, email)
+    VALUES (new.id, new.username, new.name, new.email);
+END;
+
+CREATE TRIGGER users_fts_delete AFTER DELETE ON users BEGIN
+    INSERT INTO users_fts (users_fts, rowid, username, name, email)
+    VALUES ('delete', old.id, old.username, old.name, old.email);
+END;
+
+-- 只在索引的列变化时更新，修改状态、时间戳、密码不会重建索引
+CREATE TRIGGER users_fts_update AFTER UPDATE OF username, name, email ON users BEGIN
+    INSERT INTO users_fts (users_fts, rowid, username, name, email)
+    VALUES ('delete', old.id, old.username, old.name, old.email);
+    INSERT INTO users_fts (rowid, username, name, email)
+    VALUES (new.id, new.username, new.name, new.email);
+END;
+
+-- 索引已有数据
+INSERT INTO kv_fts (kv_fts) VALUES ('rebuild');
+INSERT INTO users_fts (users_fts) VALUES ('rebuild');
@@ -0,0 +1,54 @@
+-- 变更 outbox：table_test 和 users 的增删改由触发器写入 changes，和修改本身在同一个事务里，
+-- 回滚的修改不会留下记录，命令行等其他进程的修改也会被记录。change_feed 服务按 id 顺序轮询并推送给 web socket 订阅者。
+-- AUTOINCREMENT 保证清理旧记录后 id 也不会重复使用。
+
+CREATE TABLE changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- 'kv' 或 'users'
+    table_name TEXT NOT NULL,
+    op TEXT NOT NULL CHECK (op IN ('insert', 'update', 'delete')),
+    row_id INTEGER NOT NULL,
+    -- kv 的 key 或用户的 username
+    key TEXT NOT NULL,
+    -- unix 毫秒
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX idx_changes_created_at ON changes (created_at);
+
+CREATE TRIGGER changes_kv_insert AFTER INSERT ON table_test BEGIN
+    INSERT INTO changes (table_name, op, row_id, key, created_at)
+    VALUES ('kv', 'insert', new.id, new.key,
+            CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+
+-- upsert 的 DO UPDATE 也会触发
+CREATE TRIGGER changes_kv_update AFTER UPDATE ON table_test BEGIN
+    INSERT INTO changes (table_name, op, row_id, key, created_at)
+    VALUES ('kv', 'update', new.id, new.key,
+            CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+
+CREATE TRIGGER changes_kv_delete AFTER DELETE ON table_test BEGIN
+    INSERT INTO changes (table_name, op, row_id, key, created_at)
+    VALUES ('kv', 'delete', old.id, old.key,
+            CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+
+CREATE TRIGGER changes_users_insert AFTER INSERT ON users BEGIN
+    INSERT INTO changes (table_name, op, row_id, key, created_at)
+    VALUES ('users', 'insert', new.id, new.username,
+            CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+
+CREATE TRIGGER changes_users_update AFTER UPDATE ON users BEGIN
+    INSERT INTO changes (table_name, op, row_id, key, created_at)
+    VALUES ('users', 'update', new.id, new.username,
+            CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+
+CREATE TRIGGER changes_users_delete AFTER DELETE ON users BEGIN
+    INSERT INTO changes (table_name, op, row_id, key, created_at)
+    VALUES ('users', 'delete', old.id, old.username,
+            CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
@@ -1,2 +1,15 @@
+pub mod accounts_po;
+pub mod backup;
+pub mod changes;
+pub mod kv_po;
+pub mod kv_store;
+pub mod migrate;
+pub mod products_po;
+pub mod repository;
+pub mod search;
+pub mod sqlite_async;
 pub mod sqlite_c;
+pub mod sql_stats;
+pub mod user_service;
 pub mod users_po;
+pub mod users_query;
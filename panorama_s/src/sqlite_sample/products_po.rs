@@ -0,0 +1,36 @@
+use crate::sqlite_sample::repository::{Entity, FromRow, ToRow};
+use rusqlite::{Row, ToSql};
+use serde::Serialize;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Product {
+    pub id: i64,
+    pub name: String,
+    /// 价格，单位为分
+    pub price_cents: i64,
+}
+
+impl FromRow for Product {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Product {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            price_cents: row.get("price_cents")?,
+        })
+    }
+}
+
+impl ToRow for Product {
+    fn to_row(&self) -> Vec<&dyn ToSql> {
+        vec![&self.name, &self.price_cents]
+    }
+}
+
+impl Entity for Product {
+    const TABLE: &'static str = "products";
+    const COLUMNS: &'static [&'static str] = &["name", "price_cents"];
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+}
@@ -0,0 +1,215 @@
+// 通用的单表 CRUD：实体实现 FromRow / ToRow / Entity 后即可通过 Repository 增删改查
+use crate::common::error::{AppError, AppResult};
+use crate::sqlite_sample::sql_stats;
+use crate::sqlite_sample::sqlite_c::{PooledConn, SqliteCrud, Tx};
+use anyhow::anyhow;
+use rusqlite::{params_from_iter, Connection, Row, ToSql};
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// 从查询结果的一行构造实体，按列名取值
+pub trait FromRow: Sized {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self>;
+}
+
+/// 实体除 id 以外各列的值，顺序与 Entity::COLUMNS 一致
+pub trait ToRow {
+    fn to_row(&self) -> Vec<&dyn ToSql>;
+}
+
+/// 对应一张以 INTEGER PRIMARY KEY id 为主键的表
+pub trait Entity: FromRow + ToRow {
+    const TABLE: &'static str;
+    /// 除 id 以外的列
+    const COLUMNS: &'static [&'static str];
+
+    fn id(&self) -> i64;
+}
+
+pub struct Repository<'a, T> {
+    source: Source<'a>,
+    _entity: PhantomData<T>,
+}
+
+// 连接池（每个操作单独借连接），或者事务中的连接（读写都在同一个事务里）
+enum Source<'a> {
+    Pool(&'a SqliteCrud),
+    Tx(&'a Connection),
+}
+
+// 借出的连接或事务连接，都可以当作 Connection 使用
+enum ConnRef<'a> {
+    Pooled(PooledConn<'a>),
+    Borrowed(&'a Connection),
+}
+
+impl Deref for ConnRef<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match self {
+            ConnRef::Pooled(conn) => conn,
+            ConnRef::Borrowed(conn) => conn,
+        }
+    }
+}
+
+impl<'a, T: Entity> Repository<'a, T> {
+    pub fn new(db: &'a SqliteCrud) -> Self {
+        Self {
+            source: Source::Pool(db),
+            _entity: PhantomData,
+        }
+    }
+
+    /// 在事务（或保存点）中操作，提交与回滚由外层的 transaction 决定
+    pub fn in_tx(tx: &'a Tx<'_>) -> Self {
+        Self {
+            source: Source::Tx(tx),
+            _entity: PhantomData,
+        }
+    }
+
+    fn read_conn(&self) -> AppResult<ConnRef<'a>> {
+        match self.source {
+            Source::Pool(db) => Ok(ConnRef::Pooled(db.read()?)),
+            Source::Tx(conn) => Ok(ConnRef::Borrowed(conn)),
+        }
+    }
+
+    fn write_conn(&self) -> AppResult<ConnRef<'a>> {
+        match self.source {
+            Source::Pool(db) => Ok(ConnRef::Pooled(db.write()?)),
+            Source::Tx(conn) => Ok(ConnRef::Borrowed(conn)),
+        }
+    }
+
+    fn op(name: &str) -> String {
+        format!("{}_{}", T::TABLE, name)
+    }
+
+    fn select_sql() -> String {
+        format!("SELECT id, {} FROM {}", T::COLUMNS.join(", "), T::TABLE)
+    }
+
+    // 列名会拼进 sql，只允许实体声明过的列
+    fn check_column(column: &str) -> AppResult<()> {
+        if column == "id" || T::COLUMNS.contains(&column) {
+            Ok(())
+        } else {
+            Err(AppError::Internal(anyhow!(
+                "unknown column {}.{}",
+                T::TABLE,
+                column
+            )))
+        }
+    }
+
+    fn not_found(what: String) -> AppError {
+        AppError::NotFound(format!("{} {} not found", T::TABLE, what))
+    }
+
+    /// 插入一行，忽略实体中的 id，返回新行的 id
+    pub fn insert(&self, entity: &T) -> AppResult<i64> {
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            T::TABLE,
+            T::COLUMNS.join(", "),
+            placeholders(T::COLUMNS.len())
+        );
+        let conn = self.write_conn()?;
+        let id = sql_stats::observe(&conn, &Self::op("insert"), &sql, |stmt| {
+            stmt.execute(params_from_iter(entity.to_row()))?;
+            Ok(conn.last_insert_rowid())
+        })?;
+        Ok(id)
+    }
+
+    pub fn get(&self, id: i64) -> AppResult<T> {
+        self.find_one_by("id", &id).map_err(|e| match e {
+            AppError::NotFound(_) => Self::not_found(format!("id {}", id)),
+            e => e,
+        })
+    }
+
+    pub fn list(&self) -> AppResult<Vec<T>> {
+        let sql = format!("{} ORDER BY id", Self::select_sql());
+        let conn = self.read_conn()?;
+        let rows = sql_stats::observe(&conn, &Self::op("list"), &sql, |stmt| {
+            let rows = stmt.query_map([], |row| T::from_row(row))?;
+            rows.collect::<rusqlite::Result<Vec<T>>>()
+        })?;
+        Ok(rows)
+    }
+
+    /// column = value 的第一行，没有时返回 NotFound
+    pub fn find_one_by(&self, column: &str, value: &dyn ToSql) -> AppResult<T> {
+        Self::check_column(column)?;
+        let sql = format!(
+            "{} WHERE {} = ?1 ORDER BY id LIMIT 1",
+            Self::select_sql(),
+            column
+        );
+        let conn = self.read_conn()?;
+        sql_stats::observe(&conn, &Self::op("find"), &sql, |stmt| {
+            stmt.query_row([value], |row| T::from_row(row))
+        })
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Self::not_found(format!("by {}", column)),
+            e => e.into(),
+        })
+    }
+
+    /// 按实体的 id 更新其余各列
+    pub fn update(&self, entity: &T) -> AppResult<()> {
+        let sets: Vec<String> = T::COLUMNS
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} = ?{}", c, i + 1))
+            .collect();
+        let sql = format!(
+            "UPDATE {} SET {} WHERE id = ?{}",
+            T::TABLE,
+            sets.join(", "),
+            T::COLUMNS.len() + 1
+        );
+        let id = entity.id();
+        let mut values = entity.to_row();
+        values.push(&id);
+
+        let conn = self.write_conn()?;
+        let changed = sql_stats::observe(&conn, &Self::op("update"), &sql, |stmt| {
+            stmt.execute(params_from_iter(values))
+        })?;
+        if changed == 0 {
+            return Err(Self::not_found(format!("id {}", id)));
+        }
+        Ok(())
+    }
+
+    pub fn delete(&self, id: i64) -> AppResult<()> {
+        if self.delete_by("id", &id)? == 0 {
+            return Err(Self::not_found(format!("id {}", id)));
+        }
+        Ok(())
+    }
+
+    /// 删除 column = value 的所有行，返回删除的行数
+    pub fn delete_by(&self, column: &str, value: &dyn ToSql) -> AppResult<usize> {
+        Self::check_column(column)?;
+        let sql = format!("DELETE FROM {} WHERE {} = ?1", T::TABLE, column);
+        let conn = self.write_conn()?;
+        let deleted = sql_stats::observe(&conn, &Self::op("delete"), &sql, |stmt| {
+            stmt.execute([value])
+        })?;
+        Ok(deleted)
+    }
+}
+
+// ?1, ?2, ... ?n
+fn placeholders(n: usize) -> String {
+    (1..=n)
+        .map(|i| format!("?{}", i))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,243 @@
+// 全文搜索：在 kv_fts 和 users_fts 中同时查询，按 bm25 相关度合并排序。
+// 返回的 title / snippet 是转义过的 html，命中的词用 <mark> 标出，可以直接插入页面。
+use crate::common::clock::now_ms;
+use crate::common::error::{AppError, AppResult};
+use crate::sqlite_sample::sql_stats;
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_LIMIT: usize = 20;
+pub const MAX_LIMIT: usize = 100;
+const MAX_TERMS: usize = 16;
+// highlight / snippet 先用控制字符标出命中的词，html 转义之后再换成 <mark>，
+// 否则 key、value、用户名里的 html 会原样出现在结果中
+const MARK_START: char = '\u{1}';
+const MARK_END: char = '\u{2}';
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SearchQuery {
+    /// 空格分隔的词，每个词按前缀匹配，所有词都要命中
+    pub q: String,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+impl Default for SearchQuery {
+    fn default() -> Self {
+        Self {
+            q: String::new(),
+            limit: DEFAULT_LIMIT,
+            offset: 0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    /// "kv" 或 "user"
+    pub kind: String,
+    /// table_test.id 或 users.id
+    pub id: i64,
+    /// kv 的 key 或用户的 username，已 html 转义，命中的词用 <mark> 标记
+    pub title: String,
+    /// 命中内容附近的片段，格式同 title
+    pub snippet: String,
+    /// bm25 相关度，越小越相关
+    pub rank: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchPage {
+    pub items: Vec<SearchHit>,
+    pub total: i64,
+}
+
+// 两个 fts 表各查一次后合并，?5 / ?6 是 MARK_START / MARK_END
+const SEARCH_SQL: &str = "
+    SELECT kind, id, title, snippet, rank FROM (
+        SELECT 'kv' AS kind, t.id AS id,
+               highlight(kv_fts, 0, ?5, ?6) AS title,
+               snippet(kv_fts, 1, ?5, ?6, '…', 16) AS snippet,
+               bm25(kv_fts) AS rank
+        FROM kv_fts JOIN table_test t ON t.id = kv_fts.rowid
+        WHERE kv_fts MATCH ?1 AND (t.expires_at IS NULL OR t.expires_at > ?2)
+        UNION ALL
+        SELECT 'user', u.id,
+               highlight(users_fts, 0, ?5, ?6),
+               snippet(users_fts, -1, ?5, ?6, '…', 16),
+               bm25(users_fts)
+        FROM users_fts JOIN users u ON u.id = users_fts.rowid
+        WHERE users_fts MATCH ?1
+    )
+    ORDER BY rank, kind, id
+    LIMIT ?3 OFFSET ?4";
+
+const COUNT_SQL: &str = "
+    SELECT
+        (SELECT COUNT(*) FROM kv_fts JOIN table_test t ON t.id = kv_fts.rowid
+         WHERE kv_fts MATCH ?1 AND (t.expires_at IS NULL OR t.expires_at > ?2))
+      + (SELECT COUNT(*) FROM users_fts WHERE users_fts MATCH ?1)";
+
+pub fn search(db: &SqliteCrud, query: &SearchQuery) -> AppResult<SearchPage> {
+    if query.limit == 0 || query.limit > MAX_LIMIT {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+    let expr = match_expression(&query.q)?;
+    let now = now_ms();
+
+    let conn = db.read()?;
+    let total: i64 = sql_stats::observe(&conn, "search_count", COUNT_SQL, |stmt| {
+        stmt.query_row(params![expr, now], |row| row.get(0))
+    })?;
+    let items = sql_stats::observe(&conn, "search", SEARCH_SQL, |stmt| {
+        let rows = stmt.query_map(
+            params![
+                expr,
+                now,
+                query.limit as i64,
+                query.offset as i64,
+                MARK_START.to_string(),
+                MARK_END.to_string()
+            ],
+            |row| {
+                Ok(SearchHit {
+                    kind: row.get(0)?,
+                    id: row.get(1)?,
+                    title: mark_up(&row.get::<_, String>(2)?),
+                    snippet: mark_up(&row.get::<_, Option<String>>(3)?.unwrap_or_default()),
+                    rank: row.get(4)?,
+                })
+            },
+        )?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+    Ok(SearchPage { items, total })
+}
+
+// 把用户输入转成 fts5 表达式：每个词加引号按字面匹配（避免 AND / NEAR / 列名等语法），再加 * 做前缀匹配
+fn match_expression(q: &str) -> AppResult<String> {
+    let terms: Vec<String> = q
+        .split_whitespace()
+        .take(MAX_TERMS)
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        return Err(AppError::Validation("q must not be empty".into()));
+    }
+    Ok(terms.join(" "))
+}
+
+// html 转义，再把 MARK_START / MARK_END 换成 <mark> / </mark>。
+// 原文里本来就有这两个控制字符时也只会输出配对的标签。
+fn mark_up(text: &str) -> String {
+    let mut html = String::with_capacity(text.len() + 16);
+    let mut open = false;
+    for c in text.chars() {
+        match c {
+            MARK_START if !open => {
+                html.push_str("<mark>");
+                open = true;
+            }
+            MARK_END if open => {
+                html.push_str("</mark>");
+                open = false;
+            }
+            MARK_START | MARK_END => {}
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    if open {
+        html.push_str("</mark>");
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite_sample::kv_store::KvStore;
+    use crate::sqlite_sample::migrate;
+
+    fn open_db(entries: &[(&str, &str)]) -> SqliteCrud {
+        let db = SqliteCrud::open_in_memory().unwrap();
+        migrate::apply(&db).unwrap();
+        for (key, value) in entries {
+            KvStore::new(&db).set(key, value, None).unwrap();
+        }
+        db
+    }
+
+    fn search_q(db: &SqliteCrud, q: &str) -> SearchPage {
+        let query = SearchQuery {
+            q: q.to_string(),
+            ..Default::default()
+        };
+        search(db, &query).unwrap()
+    }
+
+    #[test]
+    fn match_expression_quotes_every_term() {
+        assert_eq!(match_expression("fox").unwrap(), "\"fox\"*");
+        assert_eq!(
+            match_expression("  quick\tAND  fox ").unwrap(),
+            "\"quick\"* \"AND\"* \"fox\"*"
+        );
+        assert_eq!(match_expression("say\"hi").unwrap(), "\"say\"\"hi\"*");
+        assert_eq!(match_expression("key:value").unwrap(), "\"key:value\"*");
+        let many = vec!["t"; MAX_TERMS + 4].join(" ");
+        assert_eq!(match_expression(&many).unwrap().split(' ').count(), MAX_TERMS);
+        assert!(matches!(match_expression(" \t"), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn fts_syntax_in_query_is_literal() {
+        let db = open_db(&[("a", "NEAR the fox"), ("b", "fox")]);
+        assert_eq!(search_q(&db, "NEAR fox").total, 1);
+        assert_eq!(search_q(&db, "value:fox").total, 0);
+        // 多余的引号不会导致 fts5 语法错误，分词时当作标点忽略
+        assert_eq!(search_q(&db, "\"fox").total, 2);
+    }
+
+    #[test]
+    fn better_match_ranks_first() {
+        let db = open_db(&[
+            ("long", "a fox among many other words that are not about foxes at all"),
+            ("short", "fox fox"),
+        ]);
+        let page = search_q(&db, "fox");
+        assert_eq!(page.total, 2);
+        let titles: Vec<&str> = page.items.iter().map(|h| h.title.as_str()).collect();
+        assert_eq!(titles, vec!["short", "long"]);
+        assert!(page.items[0].rank <= page.items[1].rank);
+    }
+
+    #[test]
+    fn highlights_are_html_escaped() {
+        let db = open_db(&[(
+            "<img src=x onerror=alert(1)>",
+            "<script>alert('fox')</script> & fox",
+        )]);
+        let hit = &search_q(&db, "fox").items[0];
+        assert_eq!(hit.title, "&lt;img src=x onerror=alert(1)&gt;");
+        assert_eq!(
+            hit.snippet,
+            "&lt;script&gt;alert(&#39;<mark>fox</mark>&#39;)&lt;/script&gt; &amp; <mark>fox</mark>"
+        );
+    }
+
+    #[test]
+    fn mark_up_only_emits_paired_tags() {
+        assert_eq!(mark_up("\u{1}a\u{2}<b>"), "<mark>a</mark>&lt;b&gt;");
+        assert_eq!(mark_up("\u{2}a\u{1}\u{1}b"), "a<mark>b</mark>");
+    }
+}
@@ -0,0 +1,249 @@
+// SQL 统计：数据库操作都通过 observe 执行。它从连接的语句缓存中取出预编译语句并用 Instant 计时，
+// 同一次计时同时更新 Prometheus 指标和按 SQL 文本累计的统计，超过慢查询阈值的记 warn 日志。
+// 统计表只在出现新语句时加写锁，计数都是原子操作，连接池中并发的查询不会互相等待。
+use crate::common::metrics;
+use log::warn;
+use once_cell::sync::Lazy;
+use rusqlite::{CachedStatement, Connection};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+// 最多统计的不同语句数，超出后归入 OTHER，避免动态拼接的 SQL 让统计表无限增长
+const MAX_STATEMENTS: usize = 1000;
+const OTHER: &str = "(other statements)";
+// 慢查询日志中 SQL 的最大字符数
+const MAX_LOG_SQL: usize = 2000;
+
+static SLOW_QUERY_MS: AtomicU64 = AtomicU64::new(0);
+static STATS: Lazy<RwLock<HashMap<String, Arc<Entry>>>> = Lazy::new(Default::default);
+
+/// 一条语句的累计统计
+#[derive(Debug, Clone, Serialize)]
+pub struct StatementStats {
+    /// 空白已压缩的 SQL
+    pub sql: String,
+    /// 第一次执行时的操作名，与 Prometheus 指标的 op 标签相同
+    pub op: String,
+    /// 参数个数
+    pub params: usize,
+    pub calls: u64,
+    /// 执行失败的次数（查询无结果不算失败）
+    pub errors: u64,
+    /// 超过慢查询阈值的次数
+    pub slow_calls: u64,
+    pub total_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+struct Entry {
+    sql: String,
+    op: String,
+    params: usize,
+    calls: AtomicU64,
+    errors: AtomicU64,
+    slow_calls: AtomicU64,
+    total_ns: AtomicU64,
+    max_ns: AtomicU64,
+}
+
+impl Entry {
+    fn new(sql: &str, op: &str, params: usize) -> Self {
+        Self {
+            sql: normalize(sql),
+            op: op.to_string(),
+            params,
+            calls: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            slow_calls: AtomicU64::new(0),
+            total_ns: AtomicU64::new(0),
+            max_ns: AtomicU64::new(0),
+        }
+    }
+
+    fn add(&self, elapsed: Duration, slow: bool, failed: bool) {
+        let ns = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.errors.fetch_add(u64::from(failed), Ordering::Relaxed);
+        self.slow_calls
+            .fetch_add(u64::from(slow), Ordering::Relaxed);
+        self.total_ns.fetch_add(ns, Ordering::Relaxed);
+        self.max_ns.fetch_max(ns, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> StatementStats {
+        let calls = self.calls.load(Ordering::Relaxed);
+        let total_ms = millis(self.total_ns.load(Ordering::Relaxed));
+        StatementStats {
+            sql: self.sql.clone(),
+            op: self.op.clone(),
+            params: self.params,
+            calls,
+            errors: self.errors.load(Ordering::Relaxed),
+            slow_calls: self.slow_calls.load(Ordering::Relaxed),
+            total_ms,
+            avg_ms: total_ms / calls.max(1) as f64,
+            max_ms: millis(self.max_ns.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// 设置慢查询阈值（毫秒），0 表示不记录慢查询日志
+pub fn set_slow_query_threshold(ms: u64) {
+    SLOW_QUERY_MS.store(ms, Ordering::Relaxed);
+}
+
+pub fn slow_query_threshold() -> u64 {
+    SLOW_QUERY_MS.load(Ordering::Relaxed)
+}
+
+/// 从语句缓存中取出 sql 的预编译语句交给 f 执行，并记录耗时。
+/// op 是 Prometheus 指标的标签，计时包括取语句和 f 中读取结果的时间。
+pub fn observe<T>(
+    conn: &Connection,
+    op: &str,
+    sql: &str,
+    f: impl FnOnce(&mut CachedStatement<'_>) -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    let started = Instant::now();
+    let mut params = 0;
+    // 语句在闭包结束时 reset 并放回缓存，也计入耗时
+    let result = conn.prepare_cached(sql).and_then(|mut stmt| {
+        params = stmt.parameter_count();
+        f(&mut stmt)
+    });
+    let elapsed = started.elapsed();
+
+    let failed = metrics::record_query(op, elapsed, &result);
+    let threshold = slow_query_threshold();
+    let slow = threshold > 0 && elapsed >= Duration::from_millis(threshold);
+    entry(sql, op, params).add(elapsed, slow, failed);
+    if slow {
+        warn!(
+            "[sqlite] slow query {} {:?} params:{} sql:{}",
+            op,
+            elapsed,
+            params,
+            truncate(&normalize(sql), MAX_LOG_SQL)
+        );
+    }
+    result
+}
+
+/// 所有语句的统计，按总耗时从大到小排列
+pub fn snapshot() -> Vec<StatementStats> {
+    let mut stats: Vec<StatementStats> = STATS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .values()
+        .map(|e| e.stats())
+        .collect();
+    stats.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    stats
+}
+
+/// 清空统计
+pub fn reset() {
+    STATS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clear();
+}
+
+// 已有的语句只需要读锁；新语句加写锁插入，超出上限时归入 OTHER
+fn entry(sql: &str, op: &str, params: usize) -> Arc<Entry> {
+    if let Some(entry) = STATS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(sql)
+    {
+        return entry.clone();
+    }
+    let mut stats = STATS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (key, op, params) = if stats.len() < MAX_STATEMENTS || stats.contains_key(sql) {
+        (sql, op, params)
+    } else {
+        (OTHER, OTHER, 0)
+    };
+    stats
+        .entry(key.to_string())
+        .or_insert_with(|| Arc::new(Entry::new(key, op, params)))
+        .clone()
+}
+
+fn millis(ns: u64) -> f64 {
+    ns as f64 / 1_000_000.0
+}
+
+// 多行 SQL 压成一行，便于日志和接口展示
+fn normalize(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 统计表是进程级的，每个测试用不同的 SQL 文本，互不影响
+    fn stats_of(sql: &str) -> StatementStats {
+        let sql = normalize(sql);
+        snapshot().into_iter().find(|s| s.sql == sql).unwrap()
+    }
+
+    #[test]
+    fn counts_calls_params_and_errors() {
+        let conn = Connection::open_in_memory().unwrap();
+        let sql = "SELECT ?1 + ?2, :name, ?1 -- stats test";
+        for i in 0..3 {
+            let value: i64 = observe(&conn, "test_add", sql, |stmt| {
+                stmt.query_row(rusqlite::params![i, 1, 0], |row| row.get(0))
+            })
+            .unwrap();
+            assert_eq!(value, i + 1);
+        }
+        let bad = "SELECT * FROM missing_table -- stats test";
+        assert!(observe(&conn, "test_bad", bad, |stmt| stmt.execute([])).is_err());
+
+        let stats = stats_of(sql);
+        assert_eq!(stats.op, "test_add");
+        assert_eq!(stats.params, 3);
+        assert_eq!(stats.calls, 3);
+        assert_eq!(stats.errors, 0);
+        assert!(stats.max_ms <= stats.total_ms);
+        let stats = stats_of(bad);
+        assert_eq!((stats.calls, stats.errors), (1, 1));
+    }
+
+    #[test]
+    fn no_rows_is_not_an_error() {
+        let conn = Connection::open_in_memory().unwrap();
+        let sql = "SELECT 1 WHERE 0 -- no rows test";
+        let result = observe(&conn, "test_empty", sql, |stmt| {
+            stmt.query_row([], |row| row.get::<_, i64>(0))
+        });
+        assert!(matches!(result, Err(rusqlite::Error::QueryReturnedNoRows)));
+        assert_eq!(stats_of(sql).errors, 0);
+    }
+
+    #[test]
+    fn normalize_and_truncate() {
+        assert_eq!(
+            normalize("SELECT a,\n       b\n  FROM t"),
+            "SELECT a, b FROM t"
+        );
+        assert_eq!(truncate("héllo", 2), "hé…");
+        assert_eq!(truncate("hé", 2), "hé");
+    }
+}
@@ -0,0 +1,177 @@
+// 异步数据库接口：在 tokio 的阻塞线程池上执行 sqlite 操作，axum / web socket 处理器 await 结果，
+// 不会占住 worker 线程等待连接池或磁盘 IO。数据库由调用方传入，http 处理器从 axum 的 Extension 中取得。
+use crate::common::error::{AppError, AppResult};
+use crate::sqlite_sample::accounts_po::{self, Account};
+use crate::sqlite_sample::kv_po::KvEntry;
+use crate::sqlite_sample::kv_store::{KvStore, KvWrite};
+use crate::sqlite_sample::products_po::Product;
+use crate::sqlite_sample::repository::Repository;
+use crate::sqlite_sample::search::{self, SearchPage, SearchQuery};
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use crate::sqlite_sample::users_po::User;
+use crate::sqlite_sample::user_service::{NewUser, UserService, UserUpdate};
+use crate::sqlite_sample::users_query::{UserPage, UserQuery};
+use crate::use_sqlite;
+use anyhow::anyhow;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 在阻塞线程池上执行 f。f 内 panic 时返回 Internal 错误。
+pub async fn blocking<F, T>(f: F) -> AppResult<T>
+where
+    F: FnOnce() -> AppResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| AppError::Internal(anyhow!("db task failed: {}", e)))?
+}
+
+/// 在阻塞线程池上用 db 执行 f
+pub async fn with_handle<F, T>(db: Arc<SqliteCrud>, f: F) -> AppResult<T>
+where
+    F: FnOnce(&SqliteCrud) -> AppResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    blocking(move || f(&db)).await
+}
+
+// table_test 键值
+
+pub async fn query_data(db: Arc<SqliteCrud>, key: String) -> AppResult<String> {
+    with_handle(db, move |db| use_sqlite::query_data(db, &key)).await
+}
+
+pub async fn insert_data(db: Arc<SqliteCrud>, key: String, value: String) -> AppResult<()> {
+    with_handle(db, move |db| use_sqlite::insert_data(db, &key, &value)).await
+}
+
+pub async fn delete_data(db: Arc<SqliteCrud>, key: String) -> AppResult<usize> {
+    with_handle(db, move |db| use_sqlite::delete_data(db, &key)).await
+}
+
+// 键值存储
+
+pub async fn kv_get(db: Arc<SqliteCrud>, key: String) -> AppResult<KvEntry> {
+    with_handle(db, move |db| KvStore::new(db).get(&key)).await
+}
+
+pub async fn kv_set(
+    db: Arc<SqliteCrud>,
+    key: String,
+    value: String,
+    ttl: Option<Duration>,
+) -> AppResult<KvEntry> {
+    with_handle(db, move |db| KvStore::new(db).set(&key, &value, ttl)).await
+}
+
+pub async fn kv_compare_and_swap(
+    db: Arc<SqliteCrud>,
+    key: String,
+    expected_version: i64,
+    value: String,
+    ttl: Option<Duration>,
+) -> AppResult<KvEntry> {
+    with_handle(db, move |db| {
+        KvStore::new(db).compare_and_swap(&key, expected_version, &value, ttl)
+    })
+    .await
+}
+
+pub async fn kv_delete(db: Arc<SqliteCrud>, key: String) -> AppResult<bool> {
+    with_handle(db, move |db| KvStore::new(db).delete(&key)).await
+}
+
+pub async fn kv_scan(db: Arc<SqliteCrud>, prefix: String, limit: usize) -> AppResult<Vec<KvEntry>> {
+    with_handle(db, move |db| KvStore::new(db).scan(&prefix, limit)).await
+}
+
+pub async fn kv_get_many(db: Arc<SqliteCrud>, keys: Vec<String>) -> AppResult<Vec<KvEntry>> {
+    with_handle(db, move |db| KvStore::new(db).get_many(&keys)).await
+}
+
+pub async fn kv_set_many(db: Arc<SqliteCrud>, items: Vec<KvWrite>) -> AppResult<Vec<KvEntry>> {
+    with_handle(db, move |db| KvStore::new(db).set_many(&items)).await
+}
+
+// users
+
+/// 在阻塞线程池上用 db 上的 UserService 执行 f
+pub async fn with_users<F, T>(db: Arc<SqliteCrud>, f: F) -> AppResult<T>
+where
+    F: FnOnce(&UserService) -> AppResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    blocking(move || f(&UserService::new(db))).await
+}
+
+pub async fn query_users(db: Arc<SqliteCrud>, query: UserQuery) -> AppResult<UserPage> {
+    with_users(db, move |users| users.query(&query)).await
+}
+
+pub async fn get_user(db: Arc<SqliteCrud>, id: i64) -> AppResult<User> {
+    with_users(db, move |users| users.get(id)).await
+}
+
+pub async fn create_user(db: Arc<SqliteCrud>, new: NewUser) -> AppResult<User> {
+    with_users(db, move |users| users.create(new)).await
+}
+
+pub async fn update_user(db: Arc<SqliteCrud>, id: i64, update: UserUpdate) -> AppResult<User> {
+    with_users(db, move |users| users.update(id, update)).await
+}
+
+pub async fn delete_user(db: Arc<SqliteCrud>, id: i64) -> AppResult<()> {
+    with_users(db, move |users| users.delete(id)).await
+}
+
+// products
+
+pub async fn query_products(db: Arc<SqliteCrud>) -> AppResult<Vec<Product>> {
+    with_handle(db, |db| Repository::<Product>::new(db).list()).await
+}
+
+pub async fn get_product(db: Arc<SqliteCrud>, id: i64) -> AppResult<Product> {
+    with_handle(db, move |db| Repository::<Product>::new(db).get(id)).await
+}
+
+// 全文搜索
+
+pub async fn search(db: Arc<SqliteCrud>, query: SearchQuery) -> AppResult<SearchPage> {
+    with_handle(db, move |db| search::search(db, &query)).await
+}
+
+// accounts
+
+pub async fn query_accounts(db: Arc<SqliteCrud>) -> AppResult<Vec<Account>> {
+    with_handle(db, |db| Repository::<Account>::new(db).list()).await
+}
+
+pub async fn get_account(db: Arc<SqliteCrud>, id: i64) -> AppResult<Account> {
+    with_handle(db, move |db| Repository::<Account>::new(db).get(id)).await
+}
+
+pub async fn insert_account(
+    db: Arc<SqliteCrud>,
+    name: String,
+    balance_cents: i64,
+) -> AppResult<Account> {
+    with_handle(db, move |db| {
+        let repo = Repository::<Account>::new(db);
+        let id = repo.insert(&Account::new(&name, balance_cents))?;
+        repo.get(id)
+    })
+    .await
+}
+
+pub async fn transfer(
+    db: Arc<SqliteCrud>,
+    from: i64,
+    to: i64,
+    amount_cents: i64,
+) -> AppResult<(Account, Account)> {
+    with_handle(db, move |db| {
+        accounts_po::transfer(db, from, to, amount_cents)
+    })
+    .await
+}
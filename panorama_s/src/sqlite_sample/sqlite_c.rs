@@ -1,26 +1,271 @@
-use anyhow::Result;
+// sqlite 连接池：WAL 模式下一个写连接、多个只读连接，读写可以并行。
+// 每个连接都缓存预编译语句，语句经 sql_stats::observe 执行并计时。
+use crate::common::config::SqliteSettings;
+use crate::common::error::{AppError, AppResult};
+use crate::sqlite_sample::sql_stats;
 use log::{error, info};
-use rusqlite::Connection;
+use rusqlite::{Connection, OpenFlags};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
 pub struct SqliteCrud {
-    pub conn: Option<Connection>, // 改为 Option 以便在 drop 时 take
+    path: String,
+    writer: ConnPool,
+    readers: ConnPool,
+    acquire_timeout: Duration,
 }
 
 impl SqliteCrud {
-    pub fn new(db_path: &str) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
-        info!("[sqlite] open sqlite db ok. path:{}", db_path);
-        Ok(Self { conn: Some(conn) })
+    pub fn open(settings: &SqliteSettings) -> AppResult<Self> {
+        let busy_timeout = Duration::from_millis(settings.busy_timeout_ms);
+        sql_stats::set_slow_query_threshold(settings.slow_query_ms);
+
+        // 写连接负责创建数据库文件并切换到 WAL，之后只读连接才能打开
+        let mut writer = Connection::open(&settings.db_path)?;
+        writer.busy_timeout(busy_timeout)?;
+        prepare_conn(&mut writer, settings);
+        let mode: String = writer.query_row("PRAGMA journal_mode=WAL", [], |row| row.get(0))?;
+        writer.execute_batch("PRAGMA synchronous=NORMAL; PRAGMA foreign_keys=ON;")?;
+
+        let mut readers = Vec::with_capacity(settings.read_pool_size);
+        for _ in 0..settings.read_pool_size.max(1) {
+            let mut conn = Connection::open_with_flags(
+                &settings.db_path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | OpenFlags::SQLITE_OPEN_URI
+                    | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )?;
+            conn.busy_timeout(busy_timeout)?;
+            prepare_conn(&mut conn, settings);
+            conn.execute_batch("PRAGMA foreign_keys=ON;")?;
+            readers.push(conn);
+        }
+
+        info!(
+            "[sqlite] open sqlite db ok. path:{} journal_mode:{} readers:{}",
+            settings.db_path,
+            mode,
+            readers.len()
+        );
+        Ok(Self {
+            path: settings.db_path.clone(),
+            writer: ConnPool::new("writer", vec![writer]),
+            readers: ConnPool::new("reader", readers),
+            acquire_timeout: Duration::from_millis(settings.acquire_timeout_ms),
+        })
     }
+
+    /// 打开一个只属于这个实例的内存数据库，其余配置用默认值，主要用于测试。
+    /// 使用 sqlite 的 memdb vfs，池中的连接共享同一个数据库，实例 drop 后数据丢失。
+    pub fn open_in_memory() -> AppResult<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let settings = SqliteSettings {
+            db_path: format!(
+                "file:/panorama-{}-{}?vfs=memdb",
+                std::process::id(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            ),
+            ..SqliteSettings::default()
+        };
+        Self::open(&settings)
+    }
+
+    /// 数据库文件路径，内存数据库为 file: URI
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// 取得一个只读连接，池中没有空闲连接时最多等待 acquire_timeout
+    pub fn read(&self) -> AppResult<PooledConn<'_>> {
+        self.readers.acquire(self.acquire_timeout)
+    }
+
+    /// 取得唯一的写连接，写操作之间互斥
+    pub fn write(&self) -> AppResult<PooledConn<'_>> {
+        self.writer.acquire(self.acquire_timeout)
+    }
+
+    /// 在写连接上开启事务执行 f：f 返回 Ok 时提交，返回 Err 或 panic 时回滚。
+    /// f 内可以用 Tx::savepoint 嵌套。
+    pub fn transaction<F, T>(&self, f: F) -> AppResult<T>
+    where
+        F: FnOnce(&Tx<'_>) -> AppResult<T>,
+    {
+        let conn = self.write()?;
+        let tx = Tx {
+            conn: &conn,
+            depth: 0,
+        };
+        scoped(&conn, "BEGIN IMMEDIATE", "COMMIT", "ROLLBACK", || f(&tx))
+    }
+}
+
+fn prepare_conn(conn: &mut Connection, settings: &SqliteSettings) {
+    conn.set_prepared_statement_cache_capacity(settings.statement_cache_capacity);
 }
 
-// 实现 Drop trait 来正确关闭连接
-impl Drop for SqliteCrud {
+/// 事务中的连接，可以直接当作 Connection 使用
+pub struct Tx<'c> {
+    conn: &'c Connection,
+    depth: usize,
+}
+
+impl Tx<'_> {
+    /// 嵌套的保存点：f 失败时只回滚保存点内的修改，外层事务继续
+    pub fn savepoint<F, T>(&self, f: F) -> AppResult<T>
+    where
+        F: FnOnce(&Tx<'_>) -> AppResult<T>,
+    {
+        let depth = self.depth + 1;
+        let name = format!("sp_{}", depth);
+        let inner = Tx {
+            conn: self.conn,
+            depth,
+        };
+        scoped(
+            self.conn,
+            &format!("SAVEPOINT {}", name),
+            &format!("RELEASE {}", name),
+            &format!("ROLLBACK TO {0}; RELEASE {0}", name),
+            || f(&inner),
+        )
+    }
+}
+
+impl Deref for Tx<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn
+    }
+}
+
+// 执行 begin 后运行 f，成功时执行 commit；f 失败、commit 失败或 panic 时由 guard 执行 rollback
+fn scoped<T>(
+    conn: &Connection,
+    begin: &str,
+    commit: &str,
+    rollback: &str,
+    f: impl FnOnce() -> AppResult<T>,
+) -> AppResult<T> {
+    conn.execute_batch(begin)?;
+    let mut guard = RollbackGuard {
+        conn,
+        rollback,
+        armed: true,
+    };
+    let value = f()?;
+    conn.execute_batch(commit)?;
+    guard.armed = false;
+    Ok(value)
+}
+
+struct RollbackGuard<'a> {
+    conn: &'a Connection,
+    rollback: &'a str,
+    armed: bool,
+}
+
+impl Drop for RollbackGuard<'_> {
     fn drop(&mut self) {
-        if let Some(conn) = self.conn.take() {
+        if self.armed {
+            if let Err(e) = self.conn.execute_batch(self.rollback) {
+                error!("[sqlite] {} failed: {}", self.rollback, e);
+            }
+        }
+    }
+}
+
+struct ConnPool {
+    name: &'static str,
+    idle: Mutex<Vec<Connection>>,
+    available: Condvar,
+}
+
+impl ConnPool {
+    fn new(name: &'static str, conns: Vec<Connection>) -> Self {
+        Self {
+            name,
+            idle: Mutex::new(conns),
+            available: Condvar::new(),
+        }
+    }
+
+    // 连接归还时不会 panic，中毒的锁直接恢复使用
+    fn lock(&self) -> MutexGuard<'_, Vec<Connection>> {
+        self.idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn acquire(&self, timeout: Duration) -> AppResult<PooledConn<'_>> {
+        let deadline = Instant::now() + timeout;
+        let mut idle = self.lock();
+        loop {
+            if let Some(conn) = idle.pop() {
+                return Ok(PooledConn {
+                    pool: self,
+                    conn: Some(conn),
+                });
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(AppError::Unavailable(format!(
+                    "no idle sqlite {} connection within {:?}",
+                    self.name, timeout
+                )));
+            }
+            idle = self
+                .available
+                .wait_timeout(idle, remaining)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+    }
+
+    fn release(&self, conn: Connection) {
+        self.lock().push(conn);
+        self.available.notify_one();
+    }
+}
+
+// 正确关闭所有连接，失败时记录日志
+impl Drop for ConnPool {
+    fn drop(&mut self) {
+        for conn in self.lock().drain(..) {
             if let Err((_conn, err)) = conn.close() {
                 error!("⚠️ Failed to close SQLite connection: {}", err);
             }
         }
     }
 }
+
+/// 从池中借出的连接，drop 时自动归还
+pub struct PooledConn<'a> {
+    pool: &'a ConnPool,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConn<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection already returned")
+    }
+}
+
+impl DerefMut for PooledConn<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection already returned")
+    }
+}
+
+impl Drop for PooledConn<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.release(conn);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+// 用户服务：校验输入、维护时间戳和密码哈希，username / email 重复时返回 Conflict，用户不存在时返回 NotFound
+use crate::common::clock::now_ms;
+use crate::common::error::{AppError, AppResult};
+use crate::common::password;
+use crate::common::secret::Secret;
+use crate::sqlite_sample::repository::Repository;
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use crate::sqlite_sample::users_po::{User, UserStatus};
+use crate::sqlite_sample::users_query::{self, UserPage, UserQuery};
+use log::info;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// 新建用户的参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewUser {
+    pub username: String,
+    pub email: Option<String>,
+    /// 不填时与 username 相同
+    pub name: Option<String>,
+    /// 不填时为 0
+    #[serde(default)]
+    pub age: i32,
+    /// 不填时用户不能登录
+    pub password: Option<Secret>,
+}
+
+/// 修改用户的参数，只修改填写了的字段
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UserUpdate {
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub age: Option<i32>,
+    pub status: Option<UserStatus>,
+    pub password: Option<Secret>,
+}
+
+pub struct UserService {
+    db: Arc<SqliteCrud>,
+}
+
+impl UserService {
+    pub fn new(db: Arc<SqliteCrud>) -> Self {
+        Self { db }
+    }
+
+    pub fn create(&self, new: NewUser) -> AppResult<User> {
+        validate_username(&new.username)?;
+        let email = new.email.as_deref().map(normalize_email).transpose()?;
+        let name = match new.name {
+            Some(name) => validate_name(name)?,
+            None => new.username.clone(),
+        };
+        validate_age(new.age)?;
+        let password_hash = new.password.as_ref().map(hash_password).transpose()?;
+
+        let now = now_ms();
+        let mut user = User {
+            id: 0,
+            username: new.username,
+            email,
+            name,
+            age: new.age,
+            status: UserStatus::Active,
+            created_at: now,
+            updated_at: now,
+            password_hash,
+        };
+        user.id = Repository::<User>::new(&self.db)
+            .insert(&user)
+            .map_err(|e| unique_conflict(e, &user))?;
+
+        info!("[users] created. id:{} username:{}", user.id, user.username);
+        Ok(user)
+    }
+
+    pub fn get(&self, id: i64) -> AppResult<User> {
+        Repository::<User>::new(&self.db)
+            .get(id)
+            .map_err(|e| not_found(e, format!("user {} not found", id)))
+    }
+
+    /// 按 username 查找，不区分大小写
+    pub fn get_by_username(&self, username: &str) -> AppResult<User> {
+        Repository::<User>::new(&self.db)
+            .find_one_by("username", &username)
+            .map_err(|e| not_found(e, format!("user {} not found", username)))
+    }
+
+    pub fn list(&self) -> AppResult<Vec<User>> {
+        Repository::<User>::new(&self.db).list()
+    }
+
+    pub fn query(&self, query: &UserQuery) -> AppResult<UserPage> {
+        users_query::query_users(&self.db, query)
+    }
+
+    /// 修改用户，读取和写入在同一个事务里，返回修改后的用户
+    pub fn update(&self, id: i64, update: UserUpdate) -> AppResult<User> {
+        let email = update.email.as_deref().map(normalize_email).transpose()?;
+        let name = update.name.map(validate_name).transpose()?;
+        if let Some(age) = update.age {
+            validate_age(age)?;
+        }
+        let password_hash = update.password.as_ref().map(hash_password).transpose()?;
+
+        let user = self.db.transaction(|tx| {
+            let repo = Repository::<User>::in_tx(tx);
+            let mut user = repo
+                .get(id)
+                .map_err(|e| not_found(e, format!("user {} not found", id)))?;
+            if let Some(email) = email {
+                user.email = Some(email);
+            }
+            if let Some(name) = name {
+                user.name = name;
+            }
+            if let Some(age) = update.age {
+                user.age = age;
+            }
+            if let Some(status) = update.status {
+                user.status = status;
+            }
+            if let Some(hash) = password_hash {
+                user.password_hash = Some(hash);
+            }
+            user.updated_at = now_ms();
+            repo.update(&user).map_err(|e| unique_conflict(e, &user))?;
+            Ok(user)
+        })?;
+
+        info!("[users] updated. id:{}", id);
+        Ok(user)
+    }
+
+    pub fn set_status(&self, id: i64, status: UserStatus) -> AppResult<User> {
+        self.update(
+            id,
+            UserUpdate {
+                status: Some(status),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// 校验用户名和密码，成功时返回用户。
+    /// 用户不存在、没有密码或密码错误时返回同样的错误，耗时也相同。
+    pub fn authenticate(&self, username: &str, password: &Secret) -> AppResult<User> {
+        let user = match self.get_by_username(username) {
+            Ok(user) => Some(user),
+            Err(AppError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        // 用户不存在时也要执行 verify，不能放进 match 的 guard 里
+        let hash = user.as_ref().and_then(|u| u.password_hash.as_ref());
+        let verified = password::verify(password, hash);
+        let user = match user {
+            Some(user) if verified => user,
+            _ => {
+                info!("[users] login failed. username:{}", username);
+                return Err(AppError::Unauthorized(
+                    "invalid username or password".into(),
+                ));
+            }
+        };
+        if user.status == UserStatus::Disabled {
+            return Err(AppError::Unauthorized("user is disabled".into()));
+        }
+        info!(
+            "[users] login ok. id:{} username:{}",
+            user.id, user.username
+        );
+        Ok(user)
+    }
+
+    pub fn delete(&self, id: i64) -> AppResult<()> {
+        Repository::<User>::new(&self.db)
+            .delete(id)
+            .map_err(|e| not_found(e, format!("user {} not found", id)))?;
+        info!("[users] deleted. id:{}", id);
+        Ok(())
+    }
+}
+
+// 3 到 32 个字母、数字、_ . -
+fn validate_username(username: &str) -> AppResult<()> {
+    let valid_chars = username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+    if !(3..=32).contains(&username.len()) || !valid_chars {
+        return Err(AppError::Validation(
+            "username must be 3-32 characters of letters, digits, '_', '.' or '-'".into(),
+        ));
+    }
+    Ok(())
+}
+
+// 只做基本的格式检查，统一存小写
+fn normalize_email(email: &str) -> AppResult<String> {
+    let email = email.trim();
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => !local.is_empty() && domain.contains('.') && !domain.contains('@'),
+        None => false,
+    };
+    if !valid || email.len() > 254 || email.contains(char::is_whitespace) {
+        return Err(AppError::Validation(format!("invalid email {}", email)));
+    }
+    Ok(email.to_lowercase())
+}
+
+fn hash_password(password: &Secret) -> AppResult<Secret> {
+    password::validate(password)?;
+    password::hash(password)
+}
+
+fn validate_name(name: String) -> AppResult<String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::Validation("name must not be empty".into()));
+    }
+    Ok(name)
+}
+
+fn validate_age(age: i32) -> AppResult<()> {
+    if !(0..=150).contains(&age) {
+        return Err(AppError::Validation("age must be between 0 and 150".into()));
+    }
+    Ok(())
+}
+
+fn not_found(e: AppError, message: String) -> AppError {
+    match e {
+        AppError::NotFound(_) => AppError::NotFound(message),
+        e => e,
+    }
+}
+
+// 唯一索引冲突时说明是哪个字段重复
+fn unique_conflict(e: AppError, user: &User) -> AppError {
+    match e {
+        AppError::Conflict(msg) if msg.contains("users.username") => {
+            AppError::Conflict(format!("username {} already exists", user.username))
+        }
+        AppError::Conflict(msg) if msg.contains("users.email") => AppError::Conflict(format!(
+            "email {} already exists",
+            user.email.as_deref().unwrap_or_default()
+        )),
+        e => e,
+    }
+}
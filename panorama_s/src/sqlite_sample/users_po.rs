@@ -1,113 +1,109 @@
-use crate::sqlite_sample::sqlite_c::SqliteCrud;
-use anyhow::{bail, Result};
-use rusqlite::params;
+use crate::common::secret::Secret;
+use crate::sqlite_sample::repository::{Entity, FromRow, ToRow};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::{Row, ToSql};
+use serde::{Deserialize, Serialize};
 
-pub struct User {
-    pub id: i32,
-    pub name: String,
-    pub age: i32,
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserStatus {
+    #[default]
+    Active,
+    /// 停用的用户保留数据，但不能登录
+    Disabled,
 }
 
-impl User {
-    pub fn new(id: i32, name: String, age: i32) -> Result<Self> {
-        Ok(Self { id, name, age })
-    }
-    /// 初始化表结构
-    pub fn init_table(&self, db: &SqliteCrud) -> Result<()> {
-        if let Some(conn) = &db.conn {
-            conn.execute(
-                "CREATE TABLE IF NOT EXISTS users (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        name TEXT NOT NULL,
-                        age INTEGER NOT NULL
-                    )",
-                [],
-            )?;
-            Ok(())
-        } else {
-            bail!("Connection is None")
+impl UserStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UserStatus::Active => "active",
+            UserStatus::Disabled => "disabled",
         }
     }
+}
 
-    /// 插入一个用户
-    pub fn insert_user(&self, db: &SqliteCrud, name: &str, age: i32) -> Result<()> {
-        if let Some(conn) = &db.conn {
-            conn.execute(
-                "INSERT INTO users (name, age) VALUES (?1, ?2)",
-                params![name, age],
-            )?;
-            Ok(())
-        } else {
-            bail!("Connection is None")
-        }
+impl ToSql for UserStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
     }
+}
 
-    /// 查询所有用户
-    pub fn query_users(&self, db: &SqliteCrud) -> Result<Vec<User>> {
-        if let Some(conn) = &db.conn {
-            let mut stmt = conn.prepare("SELECT id, name, age FROM users")?;
-            let rows = stmt.query_map([], |row| {
-                Ok(User {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    age: row.get(2)?,
-                })
-            })?;
-
-            let mut users = Vec::new();
-            for user in rows {
-                users.push(user?);
-            }
-            Ok(users)
-        } else {
-            bail!("Connection is None")
+impl FromSql for UserStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "active" => Ok(UserStatus::Active),
+            "disabled" => Ok(UserStatus::Disabled),
+            other => Err(FromSqlError::Other(
+                format!("unknown user status {}", other).into(),
+            )),
         }
     }
+}
 
-    /// 更新用户信息
-    pub fn update_user(&self, db: &SqliteCrud, id: i32, name: &str, age: i32) -> Result<()> {
-        if let Some(conn) = &db.conn {
-            conn.execute(
-                "UPDATE users SET name = ?1, age = ?2 WHERE id = ?3",
-                params![name, age, id],
-            )?;
-            Ok(())
-        } else {
-            bail!("Connection is None")
-        }
-    }
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct User {
+    pub id: i64,
+    /// 登录名，不区分大小写唯一
+    pub username: String,
+    /// 不区分大小写唯一，可以为空
+    pub email: Option<String>,
+    /// 显示名
+    pub name: String,
+    pub age: i32,
+    pub status: UserStatus,
+    /// 创建、更新时间（unix 毫秒），由 UserService 维护
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// argon2 哈希，不会出现在 json 和日志里；为空时不能登录
+    #[serde(skip_serializing)]
+    pub password_hash: Option<Secret>,
+}
 
-    /// 删除用户
-    pub fn delete_user(&self, db: &SqliteCrud, id: i32) -> Result<()> {
-        if let Some(conn) = &db.conn {
-            conn.execute("DELETE FROM users WHERE id = ?1", params![id])?;
-            Ok(())
-        } else {
-            bail!("Connection is None")
-        }
+impl FromRow for User {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(User {
+            id: row.get("id")?,
+            username: row.get("username")?,
+            email: row.get("email")?,
+            name: row.get("name")?,
+            age: row.get("age")?,
+            status: row.get("status")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+            password_hash: row.get("password_hash")?,
+        })
     }
+}
 
-    // 使用事务
-    // pub fn transfer_money(
-    //     &self,
-    //     from: i32,
-    //     to: i32,
-    //     amount: i32,
-    // ) -> Result<(), rusqlite::Error> {
-    //     let conn = self.get_conn();
-    //     let tx = conn.transaction()?;
+impl ToRow for User {
+    fn to_row(&self) -> Vec<&dyn ToSql> {
+        vec![
+            &self.username,
+            &self.email,
+            &self.name,
+            &self.age,
+            &self.status,
+            &self.created_at,
+            &self.updated_at,
+            &self.password_hash,
+        ]
+    }
+}
 
-    //     // 执行转账操作
-    //     tx.execute(
-    //         "UPDATE accounts SET balance = balance - ?1 WHERE id = ?2",
-    //         [amount, from],
-    //     )?;
-    //     tx.execute(
-    //         "UPDATE accounts SET balance = balance + ?1 WHERE id = ?2",
-    //         [amount, to],
-    //     )?;
+impl Entity for User {
+    const TABLE: &'static str = "users";
+    const COLUMNS: &'static [&'static str] = &[
+        "username",
+        "email",
+        "name",
+        "age",
+        "status",
+        "created_at",
+        "updated_at",
+        "password_hash",
+    ];
 
-    //     tx.commit()?;
-    //     Ok(())
-    // }
+    fn id(&self) -> i64 {
+        self.id
+    }
 }
@@ -0,0 +1,411 @@
+// users 查询：按名字子串、年龄范围、状态过滤，按任意列排序，支持 limit/offset 和游标分页
+use crate::common::error::{AppError, AppResult};
+use crate::sqlite_sample::repository::{Entity, FromRow};
+use crate::sqlite_sample::sql_stats;
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use crate::sqlite_sample::users_po::{User, UserStatus};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rusqlite::params_from_iter;
+use rusqlite::types::Value as SqlValue;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub const DEFAULT_LIMIT: usize = 20;
+pub const MAX_LIMIT: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    #[default]
+    Id,
+    Username,
+    Name,
+    Age,
+    CreatedAt,
+}
+
+impl UserSort {
+    fn column(self) -> &'static str {
+        match self {
+            UserSort::Id => "id",
+            UserSort::Username => "username",
+            UserSort::Name => "name",
+            UserSort::Age => "age",
+            UserSort::CreatedAt => "created_at",
+        }
+    }
+
+    // 游标中保存的排序列的值
+    fn value_of(self, user: &User) -> Value {
+        match self {
+            UserSort::Id => json!(user.id),
+            UserSort::Username => json!(user.username),
+            UserSort::Name => json!(user.name),
+            UserSort::Age => json!(user.age),
+            UserSort::CreatedAt => json!(user.created_at),
+        }
+    }
+
+    fn to_sql(self, value: &Value) -> Option<SqlValue> {
+        match self {
+            UserSort::Id | UserSort::Age | UserSort::CreatedAt => {
+                value.as_i64().map(SqlValue::Integer)
+            }
+            UserSort::Username | UserSort::Name => {
+                value.as_str().map(|s| SqlValue::Text(s.to_string()))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn sql(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+
+    // 游标之后的行：升序时更大，降序时更小
+    fn after(self) -> &'static str {
+        match self {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        }
+    }
+}
+
+/// 查询条件，字段都可以省略；也直接用作 GET /users 的 query 参数
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UserQuery {
+    /// 名字包含的子串（不区分 ASCII 大小写）
+    pub name: Option<String>,
+    pub min_age: Option<i32>,
+    pub max_age: Option<i32>,
+    pub status: Option<UserStatus>,
+    pub sort: UserSort,
+    pub order: SortOrder,
+    /// 每页条数，最大 MAX_LIMIT
+    pub limit: usize,
+    /// 跳过的条数，不能和 cursor 同时使用
+    pub offset: usize,
+    /// 上一页返回的 next_cursor
+    pub cursor: Option<String>,
+}
+
+impl Default for UserQuery {
+    fn default() -> Self {
+        Self {
+            name: None,
+            min_age: None,
+            max_age: None,
+            status: None,
+            sort: UserSort::default(),
+            order: SortOrder::default(),
+            limit: DEFAULT_LIMIT,
+            offset: 0,
+            cursor: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserPage {
+    pub items: Vec<User>,
+    /// 满足过滤条件的总数，与分页无关
+    pub total: i64,
+    /// 还有下一页时返回，原样传给 cursor 即可继续
+    pub next_cursor: Option<String>,
+}
+
+// 游标记录上一页最后一行的排序值和 id，以及生成它时的排序方式
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: UserSort,
+    order: SortOrder,
+    value: Value,
+    id: i64,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(text: &str) -> AppResult<Self> {
+        URL_SAFE_NO_PAD
+            .decode(text)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| AppError::Validation("invalid cursor".into()))
+    }
+}
+
+/// 按条件查询一页用户
+pub fn query_users(db: &SqliteCrud, query: &UserQuery) -> AppResult<UserPage> {
+    if query.limit == 0 || query.limit > MAX_LIMIT {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+    if query.cursor.is_some() && query.offset > 0 {
+        return Err(AppError::Validation(
+            "cursor and offset cannot be used together".into(),
+        ));
+    }
+    if let (Some(min), Some(max)) = (query.min_age, query.max_age) {
+        if min > max {
+            return Err(AppError::Validation(
+                "min_age must not be greater than max_age".into(),
+            ));
+        }
+    }
+
+    let mut filters = Vec::new();
+    let mut args = Vec::new();
+    if let Some(name) = query.name.as_deref().filter(|n| !n.is_empty()) {
+        filters.push("name LIKE ? ESCAPE '\\'".to_string());
+        args.push(SqlValue::Text(format!("%{}%", escape_like(name))));
+    }
+    if let Some(min) = query.min_age {
+        filters.push("age >= ?".to_string());
+        args.push(SqlValue::Integer(min.into()));
+    }
+    if let Some(max) = query.max_age {
+        filters.push("age <= ?".to_string());
+        args.push(SqlValue::Integer(max.into()));
+    }
+    if let Some(status) = query.status {
+        filters.push("status = ?".to_string());
+        args.push(SqlValue::Text(status.as_str().to_string()));
+    }
+    let count_sql = format!("SELECT COUNT(*) FROM users{}", where_clause(&filters));
+
+    // 游标只影响取哪一页，不影响总数
+    let column = query.sort.column();
+    let after = query.order.after();
+    let mut page_args = args.clone();
+    if let Some(text) = &query.cursor {
+        let cursor = Cursor::decode(text)?;
+        if cursor.sort != query.sort || cursor.order != query.order {
+            return Err(AppError::Validation(
+                "cursor was created with a different sort".into(),
+            ));
+        }
+        let value = query
+            .sort
+            .to_sql(&cursor.value)
+            .ok_or_else(|| AppError::Validation("invalid cursor".into()))?;
+        if query.sort == UserSort::Id {
+            filters.push(format!("id {} ?", after));
+            page_args.push(SqlValue::Integer(cursor.id));
+        } else {
+            // 排序值相同时再按 id 排，保证翻页不重复、不遗漏
+            filters.push(format!(
+                "({0} {1} ? OR ({0} = ? AND id {1} ?))",
+                column, after
+            ));
+            page_args.push(value.clone());
+            page_args.push(value);
+            page_args.push(SqlValue::Integer(cursor.id));
+        }
+    }
+    // 多取一行用来判断是否还有下一页
+    let page_sql = format!(
+        "SELECT id, {} FROM users{} ORDER BY {} {3}, id {3} LIMIT ? OFFSET ?",
+        User::COLUMNS.join(", "),
+        where_clause(&filters),
+        column,
+        query.order.sql()
+    );
+    page_args.push(SqlValue::Integer(query.limit as i64 + 1));
+    page_args.push(SqlValue::Integer(query.offset as i64));
+
+    let conn = db.read()?;
+    let total: i64 = sql_stats::observe(&conn, "users_count", &count_sql, |stmt| {
+        stmt.query_row(params_from_iter(&args), |row| row.get(0))
+    })?;
+    let mut items = sql_stats::observe(&conn, "users_query", &page_sql, |stmt| {
+        let rows = stmt.query_map(params_from_iter(&page_args), User::from_row)?;
+        rows.collect::<rusqlite::Result<Vec<User>>>()
+    })?;
+
+    let next_cursor = if items.len() > query.limit {
+        items.truncate(query.limit);
+        items.last().map(|last| {
+            Cursor {
+                sort: query.sort,
+                order: query.order,
+                value: query.sort.value_of(last),
+                id: last.id,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+    Ok(UserPage {
+        items,
+        total,
+        next_cursor,
+    })
+}
+
+fn where_clause(filters: &[String]) -> String {
+    if filters.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", filters.join(" AND "))
+    }
+}
+
+// LIKE 中的 % _ 和转义符本身按字面匹配
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite_sample::migrate;
+    use crate::sqlite_sample::repository::Repository;
+
+    fn open_db(users: &[(&str, i32)]) -> SqliteCrud {
+        let db = SqliteCrud::open_in_memory().unwrap();
+        migrate::apply(&db).unwrap();
+        let repo = Repository::<User>::new(&db);
+        for &(name, age) in users {
+            repo.insert(&User {
+                username: name.to_string(),
+                name: name.to_string(),
+                age,
+                ..Default::default()
+            })
+            .unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = Cursor {
+            sort: UserSort::Name,
+            order: SortOrder::Desc,
+            value: json!("alice"),
+            id: 7,
+        };
+        let decoded = Cursor::decode(&cursor.encode()).unwrap();
+        assert_eq!(decoded.sort, UserSort::Name);
+        assert_eq!(decoded.order, SortOrder::Desc);
+        assert_eq!(decoded.value, json!("alice"));
+        assert_eq!(decoded.id, 7);
+    }
+
+    #[test]
+    fn invalid_cursor_is_rejected() {
+        let not_json = URL_SAFE_NO_PAD.encode("{");
+        for text in ["", "not base64!", not_json.as_str()] {
+            assert!(matches!(Cursor::decode(text), Err(AppError::Validation(_))));
+        }
+        // 排序值的类型和排序列不符
+        let cursor = Cursor {
+            sort: UserSort::Age,
+            order: SortOrder::Asc,
+            value: json!("old"),
+            id: 1,
+        };
+        let db = open_db(&[]);
+        let query = UserQuery {
+            sort: UserSort::Age,
+            cursor: Some(cursor.encode()),
+            ..Default::default()
+        };
+        assert!(matches!(query_users(&db, &query), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn cursor_pages_cover_all_rows_once() {
+        // 年龄有重复，同值的行按 id 同向排序
+        let db = open_db(&[("a", 30), ("b", 20), ("c", 30), ("d", 25), ("e", 30)]);
+        let mut query = UserQuery {
+            sort: UserSort::Age,
+            order: SortOrder::Desc,
+            limit: 2,
+            ..Default::default()
+        };
+        let mut seen = Vec::new();
+        loop {
+            let page = query_users(&db, &query).unwrap();
+            assert_eq!(page.total, 5);
+            seen.extend(page.items.iter().map(|u| u.username.clone()));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec!["e", "c", "a", "d", "b"]);
+    }
+
+    #[test]
+    fn cursor_from_another_sort_is_rejected() {
+        let db = open_db(&[("a", 30), ("b", 20)]);
+        let page = query_users(
+            &db,
+            &UserQuery {
+                limit: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let query = UserQuery {
+            sort: UserSort::Age,
+            cursor: page.next_cursor.clone(),
+            ..Default::default()
+        };
+        assert!(matches!(query_users(&db, &query), Err(AppError::Validation(_))));
+        let query = UserQuery {
+            offset: 1,
+            cursor: page.next_cursor,
+            ..Default::default()
+        };
+        assert!(matches!(query_users(&db, &query), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn name_filter_matches_wildcards_literally() {
+        assert_eq!(escape_like(r"50%_a\b"), r"50\%\_a\\b");
+        let db = open_db(&[("100%", 1), ("1000", 2), ("Al_ice", 3), ("alxice", 4)]);
+        let names = |name: &str| -> Vec<String> {
+            let query = UserQuery {
+                name: Some(name.to_string()),
+                ..Default::default()
+            };
+            query_users(&db, &query)
+                .unwrap()
+                .items
+                .into_iter()
+                .map(|u| u.username)
+                .collect()
+        };
+        assert_eq!(names("0%"), vec!["100%"]);
+        assert_eq!(names("AL_"), vec!["Al_ice"]);
+    }
+}
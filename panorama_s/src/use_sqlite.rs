@@ -1,103 +1,37 @@
-use crate::common::global;
-use anyhow::{bail, Ok, Result};
-use log::{error, info, warn};
-
-pub fn use_sqlite() -> Result<()> {
-    create_table()?;
-
-    insert_data("aaa", "aaa_value")?;
-    let result = query_data("aaa")?;
+use crate::common::error::AppResult;
+use crate::sqlite_sample::kv_store::KvStore;
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use log::info;
+
+// 表结构由 sqlite_sample::migrate 在启动时创建
+pub fn use_sqlite(db: &SqliteCrud) -> AppResult<()> {
+    insert_data(db, "aaa", "aaa_value")?;
+    let result = query_data(db, "aaa")?;
     info!("[sqlite] query_data result:{}", result);
     Ok(())
 }
 
-pub fn create_table() -> Result<()> {
-    let db = global::get_global_db()?;
-    let mut db_obj = db.lock().unwrap_or_else(|poisoned| {
-        warn!("⚠️Mutex锁中毒，强制恢复访问");
-        poisoned.into_inner() // 从中毒状态恢复数据访问
-    });
-
-    // 可以使用 CREATE TABLE IF NOT EXISTS users ？
+// 写入数据，key 已存在时原子地覆盖
+pub fn insert_data(db: &SqliteCrud, key: &str, value: &str) -> AppResult<()> {
+    let entry = KvStore::new(db).set(key, value, None)?;
+    info!(
+        "[sqlite] 插入table_test 成功。key:{} value:{} version:{}",
+        key, value, entry.version
+    );
 
-    if let Some(conn) = db_obj.conn.as_mut() {
-        let mut stmt = conn.prepare(
-            "SELECT count(*) FROM sqlite_master
-            WHERE type='table' AND name='table_test';",
-        )?;
-        let count: i64 = stmt.query_row([], |row| row.get(0))?;
-
-        if count == 0 {
-            conn.execute(
-                "CREATE TABLE table_test (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        key TEXT NOT NULL,
-                        value TEXT NULL
-                    )",
-                [],
-            )?;
-        }
-
-        Ok(())
-    } else {
-        bail!("Connection is None")
-    }
+    Ok(())
 }
 
-// 写入数据
-pub fn insert_data(key: &str, value: &str) -> Result<()> {
-    let db = global::get_global_db()?;
-    let mut db_obj = db.lock().unwrap_or_else(|poisoned| {
-        warn!("⚠️Mutex锁中毒，强制恢复访问");
-        poisoned.into_inner() // 从中毒状态恢复数据访问
-    });
+// 删除数据，返回删除的行数
+pub fn delete_data(db: &SqliteCrud, key: &str) -> AppResult<usize> {
+    let deleted = KvStore::new(db).delete(key)? as usize;
 
-    db_obj
-        .conn
-        .as_mut()
-        .ok_or_else(|| {
-            error!("取得rusqlite::Connection 可变访问出错");
-            rusqlite::Error::InvalidQuery
-        })?
-        .execute(
-            "delete from table_test where key = ?1",
-            [key],
-        )?;
-
-    info!("[sqlite] delete ok 。key:{} ", key);
-
-    db_obj
-        .conn
-        .as_mut()
-        .ok_or_else(|| {
-            error!("取得rusqlite::Connection 可变访问出错");
-            rusqlite::Error::InvalidQuery
-        })?
-        .execute(
-            "INSERT INTO table_test (key, value) VALUES (?1, ?2)",
-            [key, value],
-        )?;
-
-    info!("[sqlite] 插入table_test 成功。key:{} value:{}", key, value);
-
-    Ok(())
+    info!("[sqlite] delete ok 。key:{} rows:{}", key, deleted);
+    Ok(deleted)
 }
 
-// 读取数据
-pub fn query_data(key: &str) -> Result<String> {
-    let db = global::get_global_db()?;
-    let conn = db.lock().unwrap();
-    let value: String = conn
-        .conn
-        .as_ref()
-        .ok_or_else(|| {
-            error!("取得rusqlite::Connection 出错");
-            rusqlite::Error::InvalidQuery
-        })?
-        .query_row(
-            "SELECT value FROM table_test WHERE key = ?1",
-            [key],
-            |row| row.get(0),
-        )?;
-    Ok(value)
+// 读取数据，key 不存在或已过期时返回 NotFound
+pub fn query_data(db: &SqliteCrud, key: &str) -> AppResult<String> {
+    let entry = KvStore::new(db).get(key)?;
+    Ok(entry.value.unwrap_or_default())
 }
@@ -0,0 +1,114 @@
+// 管理接口：运行时查看、调整日志级别，在线备份数据库，查看 SQL 执行统计。
+// 所有 /admin 接口都要求 Authorization: Bearer <admin.auth_key>。
+use crate::common::error::{AppError, AppResult};
+use crate::common::global;
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use crate::sqlite_sample::{backup, sql_stats, sqlite_async};
+use anyhow::anyhow;
+use axum::http::header::AUTHORIZATION;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::{Extension, Json};
+use log::{info, warn, LevelFilter};
+use panorama_utils::logging::{LogControl, ROOT};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct SetLogLevel {
+    /// "root" 或模块路径，如 panorama_s::web_socket；不填时为 root
+    #[serde(default = "root_target")]
+    target: String,
+    /// off/error/warn/info/debug/trace，null 表示恢复配置文件中的级别
+    level: Option<LevelFilter>,
+}
+
+/// /admin 路由的鉴权中间件。没有配置 admin.auth_key 时拒绝所有请求。
+pub async fn require_key<B>(req: Request<B>, next: Next<B>) -> AppResult<Response> {
+    let Some(expected) = global::settings().admin.auth_key.as_ref() else {
+        return Err(AppError::Unauthorized(
+            "admin api is disabled, set admin.auth_key".into(),
+        ));
+    };
+    let provided = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("");
+    if !constant_time_eq(provided.as_bytes(), expected.expose().as_bytes()) {
+        warn!("[admin] rejected {} {}", req.method(), req.uri().path());
+        return Err(AppError::Unauthorized("invalid admin key".into()));
+    }
+    Ok(next.run(req).await)
+}
+
+// 比较耗时只与长度有关，不会因为前缀相同提前返回
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn root_target() -> String {
+    ROOT.to_string()
+}
+
+fn log_control() -> AppResult<&'static LogControl> {
+    global::LOG_CONTROL
+        .get()
+        .ok_or_else(|| AppError::Internal(anyhow!("LOG_CONTROL not initialized")))
+}
+
+/// GET /admin/log-level
+pub async fn get_log_levels() -> AppResult<Json<Value>> {
+    let levels = log_control()?.levels()?;
+    Ok(Json(json!(levels)))
+}
+
+/// PUT /admin/log-level  {"target": "panorama_s::web_socket", "level": "debug"}
+pub async fn set_log_level(Json(req): Json<SetLogLevel>) -> AppResult<Json<Value>> {
+    let target = req.target.trim();
+    if target.is_empty() {
+        return Err(AppError::Validation("target must not be empty".into()));
+    }
+    let control = log_control()?;
+    control.set_level(target, req.level)?;
+    info!("[admin] log level of {} set to {:?}", target, req.level);
+    Ok(Json(json!(control.levels()?)))
+}
+
+/// POST /admin/backup  在线备份，返回备份文件和被清理的旧备份
+pub async fn create_backup(Extension(db): Extension<Arc<SqliteCrud>>) -> AppResult<Json<Value>> {
+    let report =
+        sqlite_async::with_handle(db, |db| backup::create(db, &global::settings().backup)).await?;
+    Ok(Json(json!(report)))
+}
+
+/// GET /admin/backups
+pub async fn list_backups() -> AppResult<Json<Value>> {
+    let files = sqlite_async::blocking(|| {
+        let settings = global::settings();
+        backup::list(&settings.sqlite.db_path, &settings.backup)
+    })
+    .await?;
+    Ok(Json(json!(files)))
+}
+
+/// GET /admin/sql-stats  每条语句的执行次数、耗时和慢查询次数，按总耗时排序
+pub async fn get_sql_stats() -> AppResult<Json<Value>> {
+    Ok(Json(json!({
+        "slow_query_ms": sql_stats::slow_query_threshold(),
+        "statements": sql_stats::snapshot(),
+    })))
+}
+
+/// DELETE /admin/sql-stats  清空统计
+pub async fn reset_sql_stats() -> AppResult<Json<Value>> {
+    sql_stats::reset();
+    info!("[admin] sql stats reset.");
+    Ok(Json(json!({"reset": true})))
+}
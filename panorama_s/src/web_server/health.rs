@@ -0,0 +1,119 @@
+// 健康检查：/health/live 只说明进程能处理请求，/health/ready 检查依赖的组件
+use crate::common::global;
+use crate::sqlite_sample::sql_stats;
+use crate::sqlite_sample::sqlite_async;
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use crate::web_socket::ws_server;
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentState {
+    Up,
+    Down,
+    /// 配置中关闭，不参与判断
+    Disabled,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComponentStatus {
+    pub status: ComponentState,
+    /// 关键组件 down 时 readiness 返回 503
+    pub critical: bool,
+    #[serde(skip_serializing_if = "Value::is_null")]
+    pub detail: Value,
+}
+
+impl ComponentStatus {
+    fn new(status: ComponentState, critical: bool, detail: Value) -> Self {
+        Self {
+            status,
+            critical,
+            detail,
+        }
+    }
+}
+
+/// GET /health/live
+pub async fn live() -> Json<Value> {
+    Json(json!({"status": "ok"}))
+}
+
+/// GET /health/ready
+pub async fn ready(Extension(db): Extension<Arc<SqliteCrud>>) -> (StatusCode, Json<Value>) {
+    let mut components = BTreeMap::new();
+    components.insert("sqlite", check_sqlite(db).await);
+    components.insert("ws_listener", check_ws_listener());
+    components.insert(
+        "ws_connections",
+        ComponentStatus::new(
+            ComponentState::Up,
+            false,
+            json!({"active": ws_server::active_connections()}),
+        ),
+    );
+
+    let ready = !components
+        .values()
+        .any(|c| c.critical && c.status == ComponentState::Down);
+    let (status, code) = if ready {
+        ("ok", StatusCode::OK)
+    } else {
+        ("unavailable", StatusCode::SERVICE_UNAVAILABLE)
+    };
+    (
+        code,
+        Json(json!({"status": status, "components": components})),
+    )
+}
+
+// 在 db 上执行 SELECT 1
+async fn check_sqlite(db: Arc<SqliteCrud>) -> ComponentStatus {
+    let started = Instant::now();
+    let result = sqlite_async::with_handle(db, |db| {
+        let conn = db.read()?;
+        sql_stats::observe(&conn, "ping", "SELECT 1", |stmt| {
+            stmt.query_row([], |row| row.get::<_, i64>(0))
+        })?;
+        Ok(())
+    })
+    .await;
+    match result {
+        Ok(()) => ComponentStatus::new(
+            ComponentState::Up,
+            true,
+            json!({"latency_ms": started.elapsed().as_secs_f64() * 1000.0}),
+        ),
+        Err(e) => ComponentStatus::new(
+            ComponentState::Down,
+            true,
+            json!({"error": format!("{:#}", e)}),
+        ),
+    }
+}
+
+// 独立 ws 端口是否已绑定；关闭时 /ws 路由仍可用，不影响 readiness
+fn check_ws_listener() -> ComponentStatus {
+    let settings = global::settings();
+    if !settings.ws_server.enabled {
+        return ComponentStatus::new(ComponentState::Disabled, false, Value::Null);
+    }
+    let bound = ws_server::bound_listeners();
+    let expected = settings.ws_server.listen.len();
+    let status = if bound >= expected && bound > 0 {
+        ComponentState::Up
+    } else {
+        ComponentState::Down
+    };
+    ComponentStatus::new(
+        status,
+        true,
+        json!({"bound": bound, "expected": expected}),
+    )
+}
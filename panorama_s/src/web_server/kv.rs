@@ -0,0 +1,136 @@
+// 键值存储的 http 接口
+use crate::common::error::{AppError, AppResult};
+use crate::sqlite_sample::kv_store::KvWrite;
+use crate::sqlite_sample::sqlite_async;
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use axum::extract::{Path, Query};
+use axum::{Extension, Json};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 批量读写一次最多的 key 数量
+const MAX_BATCH: usize = 1000;
+const DEFAULT_SCAN_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+pub struct PutKv {
+    value: String,
+    /// 过期时间（秒），不填时不过期
+    ttl_secs: Option<u64>,
+    /// 填写时按版本号 compare-and-swap，0 表示 key 必须不存在
+    version: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ListKv {
+    /// 逗号分隔的 key 列表，填写时忽略 prefix 和 limit
+    keys: Option<String>,
+    #[serde(default)]
+    prefix: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct BatchItem {
+    key: String,
+    value: String,
+    ttl_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct BatchSet {
+    items: Vec<BatchItem>,
+}
+
+fn ttl(ttl_secs: Option<u64>) -> Option<Duration> {
+    ttl_secs.map(Duration::from_secs)
+}
+
+/// GET /kv/:key
+pub async fn get_key(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Path(key): Path<String>,
+) -> AppResult<Json<Value>> {
+    let entry = sqlite_async::kv_get(db, key).await?;
+    Ok(Json(json!(entry)))
+}
+
+/// PUT /kv/:key  {"value": "v", "ttl_secs": 60, "version": 3}
+pub async fn put_key(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Path(key): Path<String>,
+    Json(req): Json<PutKv>,
+) -> AppResult<Json<Value>> {
+    let entry = match req.version {
+        Some(version) => {
+            sqlite_async::kv_compare_and_swap(db, key, version, req.value, ttl(req.ttl_secs)).await?
+        }
+        None => sqlite_async::kv_set(db, key, req.value, ttl(req.ttl_secs)).await?,
+    };
+    Ok(Json(json!(entry)))
+}
+
+/// DELETE /kv/:key
+pub async fn delete_key(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Path(key): Path<String>,
+) -> AppResult<Json<Value>> {
+    if !sqlite_async::kv_delete(db, key.clone()).await? {
+        return Err(AppError::NotFound(format!("key {} not found", key)));
+    }
+    Ok(Json(json!({"deleted": key})))
+}
+
+/// GET /kv?prefix=user:&limit=10 或 GET /kv?keys=a,b,c
+pub async fn list(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Query(params): Query<ListKv>,
+) -> AppResult<Json<Value>> {
+    let entries = match params.keys {
+        Some(keys) => {
+            let keys: Vec<String> = keys
+                .split(',')
+                .filter(|k| !k.is_empty())
+                .map(str::to_string)
+                .collect();
+            if keys.len() > MAX_BATCH {
+                return Err(AppError::Validation(format!(
+                    "at most {} keys per request",
+                    MAX_BATCH
+                )));
+            }
+            sqlite_async::kv_get_many(db, keys).await?
+        }
+        None => {
+            let limit = params.limit.unwrap_or(DEFAULT_SCAN_LIMIT);
+            sqlite_async::kv_scan(db, params.prefix, limit).await?
+        }
+    };
+    Ok(Json(json!(entries)))
+}
+
+/// POST /kv  {"items": [{"key": "a", "value": "1", "ttl_secs": 60}]}，全部成功或全部失败
+pub async fn batch_set(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Json(req): Json<BatchSet>,
+) -> AppResult<Json<Value>> {
+    if req.items.is_empty() || req.items.len() > MAX_BATCH {
+        return Err(AppError::Validation(format!(
+            "items must contain 1 to {} entries",
+            MAX_BATCH
+        )));
+    }
+    let items = req
+        .items
+        .into_iter()
+        .map(|item| KvWrite {
+            key: item.key,
+            value: item.value,
+            ttl: ttl(item.ttl_secs),
+        })
+        .collect();
+    let entries = sqlite_async::kv_set_many(db, items).await?;
+    Ok(Json(json!(entries)))
+}
@@ -0,0 +1,42 @@
+// http 请求指标中间件和 /metrics 导出接口
+use crate::common::error::AppResult;
+use crate::common::metrics::{self, HTTP_REQUESTS_TOTAL, HTTP_REQUEST_DURATION};
+use anyhow::anyhow;
+use axum::extract::MatchedPath;
+use axum::http::{header, Request};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::time::Instant;
+
+// 没有匹配到路由的请求统一记为这个标签，避免任意路径造成标签数量膨胀
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// 按路由模板（如 /users/:id）统计请求数和耗时
+pub async fn track<B>(req: Request<B>, next: Next<B>) -> Response {
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+    let started = Instant::now();
+    let response = next.run(req).await;
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+    HTTP_REQUESTS_TOTAL
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+    response
+}
+
+/// GET /metrics
+pub async fn export() -> AppResult<Response> {
+    let body = metrics::gather().map_err(|e| anyhow!("encode metrics failed: {}", e))?;
+    Ok((
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        body,
+    )
+        .into_response())
+}
@@ -1 +1,6 @@
+pub mod admin;
+pub mod health;
+pub mod kv;
+pub mod metrics;
+pub mod request_id;
 pub mod web_server_main;
@@ -1,74 +1,219 @@
 use anyhow::{Context, Result};
 use axum::{
-    extract::{Path, Query},
-    http::HeaderMap,
-    response::{Html, Json},
+    async_trait,
+    extract::{FromRequest, Path, Query},
+    http::{header::CONTENT_TYPE, HeaderMap, Request},
+    middleware,
+    response::{Html, IntoResponse, Json, Response},
     routing::{get, post},
-    Form, Router,
+    Extension, Form, Router,
 };
+use futures::future::try_join_all;
+use futures::TryStreamExt;
+use hyper::server::accept;
 use log::info;
+use panorama_utils::shutdown::ShutdownToken;
 use serde::Deserialize;
 use serde_json::{json, Value};
-// use std::net::SocketAddr;
-use crate::common;
-use crate::use_sqlite;
+use std::sync::Arc;
+use tokio_util::task::TaskTracker;
+use crate::common::error::{AppError, AppResult};
+use crate::common::global;
+use crate::common::secret::Secret;
+use crate::common::listen::Listener;
+use crate::common::supervisor::Ready;
+use crate::sqlite_sample::search::SearchQuery;
+use crate::sqlite_sample::sqlite_async;
+use crate::sqlite_sample::sqlite_c::SqliteCrud;
+use crate::sqlite_sample::user_service::{NewUser, UserUpdate};
+use crate::sqlite_sample::users_query::UserQuery;
+use crate::web_server::{admin, health, kv, metrics, request_id};
+use crate::web_socket::ws_server::{self, WsUpgradeContext};
 
 #[derive(Deserialize)]
 struct LogIn {
     user: String,
 }
+// 不实现 Debug，避免整个请求连同密码被打进日志
 #[derive(Deserialize)]
-struct UserForm {
+struct LoginJson {
     username: String,
-    password: String,
+    password: Secret,
 }
+// 同上，不实现 Debug
 #[derive(Deserialize)]
 struct UserJson {
     username: String,
-    password: String,
+    password: Secret,
+}
+#[derive(Deserialize)]
+struct NewAccount {
+    name: String,
+    #[serde(default)]
+    balance_cents: i64,
+}
+#[derive(Deserialize)]
+struct TransferJson {
+    from: i64,
+    to: i64,
+    amount_cents: i64,
+}
+
+/// 按 Content-Type 解析表单（application/x-www-form-urlencoded）或 JSON 请求体
+struct JsonOrForm<T>(T);
+
+#[async_trait]
+impl<S, B, T> FromRequest<S, B> for JsonOrForm<T>
+where
+    Json<T>: FromRequest<S, B>,
+    Form<T>: FromRequest<S, B>,
+    B: Send + 'static,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let is_form = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+        if is_form {
+            let Form(value) = Form::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(Self(value))
+        } else {
+            let Json(value) = Json::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(Self(value))
+        }
+    }
 }
 
-pub async fn run_server() -> Result<()> {
-    let app = Router::new()
+/// 所有路由，处理器通过 Extension 取得 db
+pub fn router(db: Arc<SqliteCrud>) -> Router {
+    Router::new()
         // 首页
         .route("/", get(root))
-        .route("/login", get(log_in)) // /login?user=aaa
+        .route("/login", get(log_in).post(login)) // /login?user=aaa
         // 用户相关路由
-        .route("/users", get(list_users))
-        .route("/users/:id", get(get_user))
-        .route("/users", post(create_user))
+        .route("/users", get(list_users).post(create_user))
+        .route(
+            "/users/:id",
+            get(get_user).patch(update_user).delete(delete_user),
+        )
         .route("/users_post", post(post_user))
         // 产品相关路由
         .route("/products", get(list_products))
         .route("/products/:id", get(get_product))
+        // 账户与转账
+        .route("/accounts", get(list_accounts).post(create_account))
+        .route("/accounts/:id", get(get_account))
+        .route("/accounts/transfer", post(transfer))
+        // 全文搜索 kv 和用户
+        .route("/search", get(search))
+        // 键值存储
+        .route("/kv", get(kv::list).post(kv::batch_set))
+        .route(
+            "/kv/:key",
+            get(kv::get_key).put(kv::put_key).delete(kv::delete_key),
+        )
         // 健康检查
-        .route("/health", get(health_check))
+        .route("/health", get(health::live))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready))
+        .route("/services", get(list_services))
+        .route("/metrics", get(metrics::export))
+        // 管理接口，需要 admin.auth_key
+        .merge(admin_router())
+        // web socket，与独立的 ws_server 端口使用相同的连接处理
+        .route("/ws", get(ws_server::upgrade))
         // 演示不同响应类型
         .route("/html", get(html_response))
-        .route("/json", get(json_response));
+        .route("/json", get(json_response))
+        .layer(Extension(db))
+        .layer(middleware::from_fn(metrics::track))
+        // 最外层，保证错误响应里也能拿到 request id
+        .layer(middleware::from_fn(request_id::middleware))
+}
+
+fn admin_router() -> Router {
+    Router::new()
+        .route(
+            "/admin/log-level",
+            get(admin::get_log_levels).put(admin::set_log_level),
+        )
+        .route("/admin/backup", post(admin::create_backup))
+        .route("/admin/backups", get(admin::list_backups))
+        .route(
+            "/admin/sql-stats",
+            get(admin::get_sql_stats).delete(admin::reset_sql_stats),
+        )
+        // route_layer 只作用于匹配到的路由，未知的 /admin 路径仍然返回 404
+        .route_layer(middleware::from_fn(admin::require_key))
+}
+
+/// 按配置绑定 web_server.listen 中的所有地址并启动服务器，绑定成功后通知就绪
+pub async fn run_server(db: Arc<SqliteCrud>, shutdown: ShutdownToken, ready: Ready) -> Result<()> {
+    let listeners = Listener::bind_all(&global::settings().web_server.listen).await?;
+    ready.ready();
+    serve(db, listeners, shutdown).await
+}
 
-    // 启动服务器
-    // let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    let addr = "127.0.0.1:3000".parse()?;
-    println!("Server listening on {}", addr);
+/// 在已绑定的监听器上启动服务器，测试时可以传入绑定在随机端口上的监听器
+pub async fn serve(
+    db: Arc<SqliteCrud>,
+    listeners: Vec<Listener>,
+    shutdown: ShutdownToken,
+) -> Result<()> {
+    let ws_ctx = WsUpgradeContext {
+        shutdown: shutdown.clone(),
+        tracker: TaskTracker::new(),
+    };
+    let app = router(db).layer(Extension(ws_ctx.clone()));
+    let mut servers = Vec::with_capacity(listeners.len());
+    for listener in listeners {
+        let addr = listener.local_addr();
+        info!("Server listening on {}", addr);
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .context("Failed to start server")?;
+        let incoming = accept::from_stream(listener.into_stream().map_ok(|(stream, _)| stream));
+        let token = shutdown.clone();
+        // 收到关闭通知后停止接收新连接，等待处理中的请求完成
+        let server = axum::Server::builder(incoming)
+            .serve(app.clone().into_make_service())
+            .with_graceful_shutdown(async move { token.cancelled().await });
+        servers.push(async move {
+            server
+                .await
+                .with_context(|| format!("web server on {} failed", addr))
+        });
+    }
+
+    try_join_all(servers).await?;
 
+    // http 请求已经处理完，再等待 /ws 升级出来的连接发送 Close 后退出
+    ws_ctx.tracker.close();
+    info!("web_server draining {} ws connections", ws_ctx.tracker.len());
+    ws_ctx.tracker.wait().await;
+    info!("web_server stopped");
     Ok(())
 }
 
 // 处理器函数
 
-async fn log_in(Query(params): Query<LogIn>, headers: HeaderMap) -> Json<Value> {
+async fn log_in(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Query(params): Query<LogIn>,
+    headers: HeaderMap,
+) -> AppResult<Json<Value>> {
     info!("log in param user: {}", params.user);
 
     //  let user_agent = headers.get("User-Agent")        // Option<&HeaderValue>
-    //     .ok_or(common::MyError::MissingValue)?      // 转为 Result<&HeaderValue, MyError>
+    //     .ok_or_else(|| AppError::Validation("missing User-Agent".into()))?  // 转为 AppResult<&HeaderValue>
     //     .to_str()                                   //Result<&str, ToStrError>
-    //     .map_err(|_| common::MyError::InvalidValue)?;       // 最终 Result<&str, MyError>
+    //     .map_err(|_| AppError::Validation("invalid User-Agent".into()))?;   // 最终 AppResult<&str>
     // info!("User-Agent: {}", user_agent);
 
     let user_agent = headers
@@ -84,30 +229,22 @@ async fn log_in(Query(params): Query<LogIn>, headers: HeaderMap) -> Json<Value>
         .unwrap_or("");
     info!("Accept: {}", accept);
 
-    match use_sqlite::query_data(&params.user) {
-        Ok(data) if !data.is_empty() => {
-            if data == "aaa_value" {
-                info!("查询ok");
-                Json(json!([
-                    {"ret": "ok"}
-                ]))
-            } else {
-                info!("非预期值: {}", data);
-                Json(json!([{"ret": "unexpected"}]))
-            }
-        }
-        Ok(_) => {
-            info!("null");
-            Json(json!([
-                {"ret": "null."}
-            ]))
-        }
-        Err(e) => {
-            info!("错误：{}", e);
-            Json(json!([
-                {"ret": "failed."}
-            ]))
-        }
+    // 查询失败（包括 key 不存在）由 AppError 转成对应状态码的 json 错误
+    let data = sqlite_async::query_data(db, params.user.clone()).await?;
+    if data.is_empty() {
+        info!("null");
+        return Ok(Json(json!([
+            {"ret": "null."}
+        ])));
+    }
+    if data == "aaa_value" {
+        info!("查询ok");
+        Ok(Json(json!([
+            {"ret": "ok"}
+        ])))
+    } else {
+        info!("非预期值: {}", data);
+        Ok(Json(json!([{"ret": "unexpected"}])))
     }
 }
 
@@ -115,45 +252,143 @@ async fn root() -> Html<&'static str> {
     Html("<h1>Welcome to the Rust Web Server!</h1>")
 }
 
-async fn list_users() -> Json<Value> {
-    Json(json!([
-        {"id": 1, "name": "Alice"},
-        {"id": 2, "name": "Bob"}
-    ]))
+// /users?name=al&min_age=18&max_age=30&sort=age&order=desc&limit=20&cursor=...
+async fn list_users(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Query(query): Query<UserQuery>,
+) -> AppResult<Json<Value>> {
+    let page = sqlite_async::query_users(db, query).await?;
+    Ok(Json(json!(page)))
+}
+
+async fn get_user(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Path(id): Path<i64>,
+) -> AppResult<Json<Value>> {
+    let user = sqlite_async::get_user(db, id).await?;
+    Ok(Json(json!(user)))
+}
+
+// {"username": "alice", "email": "alice@example.com", "name": "Alice", "age": 20, "password": "..."}
+// 也接受表单：username=alice&password=...
+async fn create_user(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    JsonOrForm(new): JsonOrForm<NewUser>,
+) -> AppResult<Json<Value>> {
+    let user = sqlite_async::create_user(db, new).await?;
+    Ok(Json(json!(user)))
+}
+
+// {"username": "alice", "password": "..."}，与 POST /users 一样经 UserService 哈希密码后保存
+async fn post_user(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Json(json): Json<UserJson>,
+) -> AppResult<Json<Value>> {
+    info!("post2 Received username: {}", json.username);
+    let new = NewUser {
+        username: json.username,
+        email: None,
+        name: None,
+        age: 0,
+        password: Some(json.password),
+    };
+    let user = sqlite_async::create_user(db, new).await?;
+    Ok(Json(json!({"status": "user post", "id": user.id})))
+}
+
+// 只修改填写了的字段：{"email": ..., "name": ..., "age": ..., "status": "disabled"}
+async fn update_user(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Path(id): Path<i64>,
+    Json(update): Json<UserUpdate>,
+) -> AppResult<Json<Value>> {
+    let user = sqlite_async::update_user(db, id, update).await?;
+    Ok(Json(json!(user)))
+}
+
+async fn delete_user(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Path(id): Path<i64>,
+) -> AppResult<Json<Value>> {
+    sqlite_async::delete_user(db, id).await?;
+    Ok(Json(json!({"deleted": id})))
+}
+// {"username": "alice", "password": "..."}，用户名或密码错误时返回 401
+async fn login(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Json(req): Json<LoginJson>,
+) -> AppResult<Json<Value>> {
+    info!("login request from user: {}", req.username);
+    let user =
+        sqlite_async::with_users(db, move |users| users.authenticate(&req.username, &req.password))
+            .await?;
+    Ok(Json(json!({"id": user.id, "username": user.username})))
+}
+
+async fn list_products(Extension(db): Extension<Arc<SqliteCrud>>) -> AppResult<Json<Value>> {
+    let products = sqlite_async::query_products(db).await?;
+    Ok(Json(json!(products)))
+}
+
+async fn get_product(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Path(id): Path<i64>,
+) -> AppResult<Json<Value>> {
+    let product = sqlite_async::get_product(db, id).await?;
+    Ok(Json(json!(product)))
 }
 
-async fn get_user(Path(id): Path<u32>) -> Json<Value> {
-    Json(json!({"id": id, "name": format!("User {}", id)}))
+async fn list_accounts(Extension(db): Extension<Arc<SqliteCrud>>) -> AppResult<Json<Value>> {
+    let accounts = sqlite_async::query_accounts(db).await?;
+    Ok(Json(json!(accounts)))
 }
 
-async fn create_user(Form(form): Form<UserForm>) -> Json<Value> {
-    info!(
-        "post1 Received username: {}, password: {}",
-        form.username, form.password
-    );
-    Json(json!({"status": "user created"}))
+async fn get_account(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Path(id): Path<i64>,
+) -> AppResult<Json<Value>> {
+    let account = sqlite_async::get_account(db, id).await?;
+    Ok(Json(json!(account)))
 }
-async fn post_user(Json(json): Json<UserJson>) -> Json<Value> {
-    info!(
-        "post2 Received username: {}, password: {}",
-        json.username, json.password
-    );
-    Json(json!({"status": "user post"}))
+
+async fn create_account(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Json(req): Json<NewAccount>,
+) -> AppResult<Json<Value>> {
+    if req.name.trim().is_empty() {
+        return Err(AppError::Validation("name must not be empty".into()));
+    }
+    if req.balance_cents < 0 {
+        return Err(AppError::Validation("balance must not be negative".into()));
+    }
+    let account = sqlite_async::insert_account(db, req.name, req.balance_cents).await?;
+    Ok(Json(json!(account)))
 }
 
-async fn list_products() -> Json<Value> {
-    Json(json!([
-        {"id": 1, "name": "Rust Book"},
-        {"id": 2, "name": "Axum Guide"}
-    ]))
+// 两个账户的余额在同一个事务里修改
+async fn transfer(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Json(req): Json<TransferJson>,
+) -> AppResult<Json<Value>> {
+    let (from, to) = sqlite_async::transfer(db, req.from, req.to, req.amount_cents).await?;
+    Ok(Json(json!({"from": from, "to": to})))
 }
 
-async fn get_product(Path(id): Path<u32>) -> Json<Value> {
-    Json(json!({"id": id, "name": format!("Product {}", id)}))
+// /search?q=alice&limit=20&offset=0
+async fn search(
+    Extension(db): Extension<Arc<SqliteCrud>>,
+    Query(query): Query<SearchQuery>,
+) -> AppResult<Json<Value>> {
+    let page = sqlite_async::search(db, query).await?;
+    Ok(Json(json!(page)))
 }
 
-async fn health_check() -> &'static str {
-    "OK"
+// supervisor 管理的服务状态
+async fn list_services() -> Json<Value> {
+    match global::SERVICES.get() {
+        Some(registry) => Json(json!(registry.snapshot())),
+        None => Json(json!({})),
+    }
 }
 
 async fn html_response() -> Html<&'static str> {
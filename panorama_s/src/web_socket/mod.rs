@@ -1 +1,2 @@
+pub mod subscriptions;
 pub mod ws_server;
\ No newline at end of file
@@ -0,0 +1,197 @@
+// web socket 连接上的变更订阅，协议都是 JSON 文本消息：
+//   {"type": "subscribe", "table": "kv", "prefix": "user:"}
+//     -> {"type": "subscribed", "id": 1, "table": "kv", "prefix": "user:"}
+//   {"type": "unsubscribe", "id": 1}  -> {"type": "unsubscribed", "id": 1}
+// 订阅后，匹配的变更推送为
+//   {"type": "change", "subscriptions": [1], "change": {"id": 42, "table": "kv", "op": "update", "row_id": 7, "key": "user:1", "at": ...}}
+// 处理不过来丢了事件时推送 {"type": "lagged", "missed": n}，客户端需要重新读取数据。
+// type 不是 subscribe / unsubscribe 的消息不属于订阅协议，由调用方按原来的方式处理。
+use crate::sqlite_sample::changes::{self, ChangeEvent};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+/// 每个连接最多的订阅数
+const MAX_SUBSCRIPTIONS: usize = 32;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    Subscribe {
+        /// kv 或 users
+        table: String,
+        /// kv 的 key 或用户的 username 前缀，不填时匹配全部
+        #[serde(default)]
+        prefix: String,
+    },
+    Unsubscribe {
+        id: u64,
+    },
+}
+
+struct Subscription {
+    id: u64,
+    table: String,
+    prefix: String,
+}
+
+/// 一个连接的所有订阅。没有订阅时不接收广播，不占用通道。
+#[derive(Default)]
+pub struct Subscriptions {
+    next_id: u64,
+    items: Vec<Subscription>,
+    feed: Option<Receiver<Arc<ChangeEvent>>>,
+}
+
+impl Subscriptions {
+    /// 处理订阅协议消息并返回回复；不是订阅协议的消息返回 None
+    pub fn handle(&mut self, text: &str) -> Option<Value> {
+        let value: Value = serde_json::from_str(text).ok()?;
+        if !matches!(
+            value.get("type").and_then(Value::as_str),
+            Some("subscribe" | "unsubscribe")
+        ) {
+            return None;
+        }
+        let reply = match serde_json::from_value(value) {
+            Ok(Request::Subscribe { table, prefix }) => self.subscribe(table, prefix),
+            Ok(Request::Unsubscribe { id }) => self.unsubscribe(id),
+            Err(e) => Err(format!("invalid request: {}", e)),
+        };
+        Some(reply.unwrap_or_else(|message| json!({"type": "error", "message": message})))
+    }
+
+    fn subscribe(&mut self, table: String, prefix: String) -> Result<Value, String> {
+        if !changes::TABLES.contains(&table.as_str()) {
+            return Err(format!(
+                "unknown table {}, expected one of {:?}",
+                table,
+                changes::TABLES
+            ));
+        }
+        if self.items.len() >= MAX_SUBSCRIPTIONS {
+            return Err(format!(
+                "at most {} subscriptions per connection",
+                MAX_SUBSCRIPTIONS
+            ));
+        }
+        self.next_id += 1;
+        let reply =
+            json!({"type": "subscribed", "id": self.next_id, "table": table, "prefix": prefix});
+        self.items.push(Subscription {
+            id: self.next_id,
+            table,
+            prefix,
+        });
+        self.feed.get_or_insert_with(changes::subscribe);
+        Ok(reply)
+    }
+
+    fn unsubscribe(&mut self, id: u64) -> Result<Value, String> {
+        let before = self.items.len();
+        self.items.retain(|s| s.id != id);
+        if self.items.len() == before {
+            return Err(format!("subscription {} not found", id));
+        }
+        if self.items.is_empty() {
+            self.feed = None;
+        }
+        Ok(json!({"type": "unsubscribed", "id": id}))
+    }
+
+    /// 等待下一条要推送给客户端的消息。没有订阅时一直等待，可以直接放进 select!。
+    pub async fn next_message(&mut self) -> Value {
+        loop {
+            let Some(feed) = self.feed.as_mut() else {
+                return std::future::pending().await;
+            };
+            match feed.recv().await {
+                Ok(event) => {
+                    let ids = self.matching(&event);
+                    if !ids.is_empty() {
+                        return json!({"type": "change", "subscriptions": ids, "change": *event});
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    return json!({"type": "lagged", "missed": missed});
+                }
+                // 发送端是全局的，不会关闭；万一关闭就不再推送
+                Err(RecvError::Closed) => self.feed = None,
+            }
+        }
+    }
+
+    // 匹配事件的订阅 id，按订阅顺序排列
+    fn matching(&self, event: &ChangeEvent) -> Vec<u64> {
+        self.items
+            .iter()
+            .filter(|s| s.table == event.table && event.key.starts_with(&s.prefix))
+            .map(|s| s.id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(table: &str, key: &str) -> ChangeEvent {
+        ChangeEvent {
+            id: 1,
+            table: table.to_string(),
+            op: "update".to_string(),
+            row_id: 1,
+            key: key.to_string(),
+            at: 0,
+        }
+    }
+
+    fn subscribe(subs: &mut Subscriptions, table: &str, prefix: &str) -> Value {
+        let text = json!({"type": "subscribe", "table": table, "prefix": prefix}).to_string();
+        subs.handle(&text).unwrap()
+    }
+
+    #[test]
+    fn matches_by_table_and_key_prefix() {
+        let mut subs = Subscriptions::default();
+        assert_eq!(subscribe(&mut subs, "kv", "user:")["id"], 1);
+        assert_eq!(subscribe(&mut subs, "kv", "")["id"], 2);
+        assert_eq!(subscribe(&mut subs, "users", "ad")["id"], 3);
+
+        assert_eq!(subs.matching(&event("kv", "user:1")), [1, 2]);
+        assert_eq!(subs.matching(&event("kv", "session:1")), [2]);
+        assert_eq!(subs.matching(&event("users", "admin")), [3]);
+        assert!(subs.matching(&event("users", "bob")).is_empty());
+    }
+
+    #[test]
+    fn unsubscribe_stops_matching_and_releases_feed() {
+        let mut subs = Subscriptions::default();
+        subscribe(&mut subs, "kv", "a");
+        assert!(subs.feed.is_some());
+        let reply = subs.handle(r#"{"type": "unsubscribe", "id": 1}"#).unwrap();
+        assert_eq!(reply, json!({"type": "unsubscribed", "id": 1}));
+        assert!(subs.matching(&event("kv", "a")).is_empty());
+        assert!(subs.feed.is_none());
+
+        let reply = subs.handle(r#"{"type": "unsubscribe", "id": 1}"#).unwrap();
+        assert_eq!(reply["type"], "error");
+    }
+
+    #[test]
+    fn rejects_bad_requests_and_ignores_other_messages() {
+        let mut subs = Subscriptions::default();
+        assert!(subs.handle("hello").is_none());
+        assert!(subs.handle(r#"{"type": "ping"}"#).is_none());
+        assert_eq!(subscribe(&mut subs, "orders", "")["type"], "error");
+        assert_eq!(
+            subs.handle(r#"{"type": "subscribe"}"#).unwrap()["type"],
+            "error"
+        );
+        for _ in 0..MAX_SUBSCRIPTIONS {
+            assert_eq!(subscribe(&mut subs, "kv", "")["type"], "subscribed");
+        }
+        assert_eq!(subscribe(&mut subs, "kv", "")["type"], "error");
+    }
+}
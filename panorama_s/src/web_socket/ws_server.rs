@@ -1,35 +1,97 @@
-use anyhow::Result;
-use core::option::Option::None;
-use futures_util::{stream::SplitSink, SinkExt, StreamExt};
-use log::{error, info};
-use std::error::Error;
-use tokio::net::TcpStream;
-use tokio::sync::mpsc;
+use crate::common::global;
+use crate::common::metrics::{self, GaugeGuard};
+use crate::common::listen::{self, Listener};
+use crate::common::supervisor::Ready;
+use crate::web_socket::subscriptions::Subscriptions;
+use anyhow::{Context, Result};
+use axum::{
+    extract::ws::{self, WebSocketUpgrade},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use futures_util::{future, stream::select_all, Sink, SinkExt, Stream, StreamExt};
+use log::{debug, error, info, warn};
+use panorama_utils::shutdown::ShutdownToken;
+use tokio::task::JoinSet;
 use tokio::time::{timeout, Duration};
-use tokio_tungstenite::{accept_async, tungstenite::Message, WebSocketStream};
+use std::fmt;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use tokio_util::task::TaskTracker;
+use tokio_tungstenite::{accept_async, tungstenite::Message};
 
-// 设置读写超时（毫秒）
+// 设置读超时（毫秒）
 const READ_TIMEOUT_MS: u64 = 5000;
-const WRITE_TIMEOUT_MS: u64 = 5000;
+// accept 暂时出错（如文件描述符耗尽）后等待一会儿再继续接收
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
 
-async fn handle_connection(ws_stream: WebSocketStream<TcpStream>) {
-    // 拆分成读/写两端
-    let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+/// 独立端口当前已绑定的监听器数量，serve 退出后归零
+pub fn bound_listeners() -> usize {
+    metrics::WS_LISTENERS_BOUND.get().max(0) as usize
+}
+
+/// 当前活动的 web socket 连接数（包括 /ws 路由）
+pub fn active_connections() -> usize {
+    metrics::WS_CONNECTIONS_ACTIVE.get().max(0) as usize
+}
 
-    // 持续监听接收消息
-    while let Some(result) = ws_receiver.next().await {
+/// 独立监听端口和 axum 的 /ws 路由共用的连接处理逻辑。
+/// 两边的 web socket 类型不同，统一成收发 tungstenite Message 的读/写两端。
+async fn handle_connection<Si, St, E>(mut ws_sender: Si, mut ws_receiver: St, shutdown: ShutdownToken)
+where
+    Si: Sink<Message, Error = E> + Unpin,
+    St: Stream<Item = Result<Message, E>> + Unpin,
+    E: fmt::Display,
+{
+    metrics::WS_CONNECTIONS_TOTAL.inc();
+    let _active = GaugeGuard::add(&metrics::WS_CONNECTIONS_ACTIVE, 1);
+    let messages_in = metrics::WS_MESSAGES_TOTAL.with_label_values(&["in"]);
+    let messages_out = metrics::WS_MESSAGES_TOTAL.with_label_values(&["out"]);
+    let mut subscriptions = Subscriptions::default();
+
+    // 持续监听接收消息，直到连接断开或服务关闭
+    loop {
+        let result = tokio::select! {
+            next = ws_receiver.next() => match next {
+                Some(result) => result,
+                None => break,
+            },
+            // 推送订阅的数据变更
+            push = subscriptions.next_message() => {
+                if ws_sender.send(Message::Text(push.to_string())).await.is_err() {
+                    break;
+                }
+                messages_out.inc();
+                continue;
+            }
+            _ = shutdown.cancelled() => {
+                going_away(&mut ws_sender, &mut ws_receiver).await;
+                return;
+            }
+        };
+        if result.is_ok() {
+            messages_in.inc();
+        }
         match result {
             Ok(Message::Text(text)) => {
-                let ret_msg = Message::Text(format!("{}_ret", text));
+                // 订阅协议的消息回复处理结果，其他消息照旧回显
+                let ret_msg = match subscriptions.handle(&text) {
+                    Some(reply) => Message::Text(reply.to_string()),
+                    None => Message::Text(format!("{}_ret", text)),
+                };
                 if ws_sender.send(ret_msg).await.is_err() {
                     break;
                 }
+                messages_out.inc();
             }
             Ok(Message::Binary(bin)) => {
                 info!("Received binary (len={})", bin.len())
             }
             Ok(Message::Ping(ping)) => {
-                let _ = ws_sender.send(Message::Pong(ping)).await;
+                if ws_sender.send(Message::Pong(ping)).await.is_err() {
+                    break;
+                }
+                messages_out.inc();
             }
             Ok(Message::Close(_)) => break,
             Err(e) => {
@@ -41,16 +103,159 @@ async fn handle_connection(ws_stream: WebSocketStream<TcpStream>) {
     }
     // let _ = ws_sender.close().await; // 确保连接关闭
     match ws_sender.close().await {
-        Ok(_) => println!("ws sender成功关闭"),
+        Ok(_) => debug!("ws sender成功关闭"),
         Err(e) => error!("ws sender关闭失败：{}", e),
     }
 }
 
-pub async fn run_server() -> Result<(), Box<dyn Error>> {
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await?;
-    while let Ok((stream, _)) = listener.accept().await {
-        let ws_stream = accept_async(stream).await?;
-        tokio::spawn(handle_connection(ws_stream));
+// 服务关闭时发送 1001 Close 帧，并等待客户端回应完成关闭握手
+async fn going_away<Si, St, E>(ws_sender: &mut Si, ws_receiver: &mut St)
+where
+    Si: Sink<Message, Error = E> + Unpin,
+    St: Stream<Item = Result<Message, E>> + Unpin,
+    E: fmt::Display,
+{
+    let frame = CloseFrame {
+        code: CloseCode::Away,
+        reason: "going away".into(),
+    };
+    if let Err(e) = ws_sender.send(Message::Close(Some(frame))).await {
+        error!("发送 Close 帧失败：{}", e);
+        return;
+    }
+
+    let wait_close = async {
+        while let Some(Ok(msg)) = ws_receiver.next().await {
+            if msg.is_close() {
+                break;
+            }
+        }
+    };
+    if timeout(Duration::from_millis(READ_TIMEOUT_MS), wait_close)
+        .await
+        .is_err()
+    {
+        warn!("等待客户端 Close 回应超时");
+    }
+}
+
+/// axum 路由升级上来的连接所需的上下文，由 web server 通过 Extension 注入
+#[derive(Debug, Clone)]
+pub struct WsUpgradeContext {
+    pub shutdown: ShutdownToken,
+    /// 跟踪升级后的连接，web server 关闭时等待它们结束
+    pub tracker: TaskTracker,
+}
+
+/// GET /ws：握手由 axum 的 WebSocketUpgrade 完成（非法的升级请求由它直接拒绝），之后交给 handle_connection
+pub async fn upgrade(Extension(ctx): Extension<WsUpgradeContext>, ws: WebSocketUpgrade) -> Response {
+    if ctx.shutdown.is_cancelled() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "shutting down").into_response();
+    }
+    // 响应发出前就计入 tracker，关闭时不会漏掉正在升级的连接
+    let token = ctx.tracker.token();
+    let shutdown = ctx.shutdown;
+    ws.on_upgrade(move |socket| async move {
+        let (sink, stream) = socket.split();
+        let sink = sink.with(|msg| future::ready(into_axum(msg)));
+        let stream = stream.map(|msg| msg.map(from_axum));
+        handle_connection(sink, stream, shutdown).await;
+        drop(token);
+    })
+}
+
+// axum 和 tungstenite 的消息类型互转
+fn from_axum(msg: ws::Message) -> Message {
+    match msg {
+        ws::Message::Text(text) => Message::Text(text),
+        ws::Message::Binary(bin) => Message::Binary(bin),
+        ws::Message::Ping(ping) => Message::Ping(ping),
+        ws::Message::Pong(pong) => Message::Pong(pong),
+        ws::Message::Close(frame) => Message::Close(frame.map(|f| CloseFrame {
+            code: CloseCode::from(f.code),
+            reason: f.reason,
+        })),
+    }
+}
+
+fn into_axum(msg: Message) -> Result<ws::Message, axum::Error> {
+    Ok(match msg {
+        Message::Text(text) => ws::Message::Text(text),
+        Message::Binary(bin) => ws::Message::Binary(bin),
+        Message::Ping(ping) => ws::Message::Ping(ping),
+        Message::Pong(pong) => ws::Message::Pong(pong),
+        Message::Close(frame) => ws::Message::Close(frame.map(|f| ws::CloseFrame {
+            code: f.code.into(),
+            reason: f.reason,
+        })),
+        Message::Frame(_) => return Err(axum::Error::new("raw frames are not supported")),
+    })
+}
+
+/// 按配置绑定 ws_server.listen 中的所有地址并启动服务器，绑定成功后通知就绪
+pub async fn run_server(shutdown: ShutdownToken, ready: Ready) -> Result<()> {
+    let listeners = Listener::bind_all(&global::settings().ws_server.listen).await?;
+    ready.ready();
+    serve(listeners, shutdown).await
+}
+
+/// 在已绑定的监听器上接受 web socket 连接，测试时可以传入绑定在随机端口上的监听器
+pub async fn serve(listeners: Vec<Listener>, shutdown: ShutdownToken) -> Result<()> {
+    for listener in &listeners {
+        info!("WebSocket server listening on {}", listener.local_addr());
+    }
+    let bound = GaugeGuard::add(&metrics::WS_LISTENERS_BOUND, listeners.len() as i64);
+    // 合并所有监听器的连接
+    let mut incoming = select_all(listeners.into_iter().map(Listener::into_stream));
+
+    // 连接只等 closing：服务关闭和监听器失效时都由这里通知连接发送 Close 并排空
+    let closing = ShutdownToken::new();
+    let mut connections = JoinSet::new();
+    let mut failed = None;
+    loop {
+        tokio::select! {
+            Some(accepted) = incoming.next() => {
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) if listen::is_fatal_accept_error(&e) => {
+                        // 排空现有连接后交给 supervisor 重启
+                        error!("ws_server listener failed: {}", e);
+                        failed = Some(e);
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("ws_server accept failed: {}", e);
+                        tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                        continue;
+                    }
+                };
+                // 握手放到连接任务里，单个客户端失败不影响监听
+                let closing = closing.clone();
+                connections.spawn(async move {
+                    match accept_async(stream).await {
+                        Ok(ws_stream) => {
+                            let (sink, stream) = ws_stream.split();
+                            handle_connection(sink, stream, closing).await
+                        }
+                        Err(e) => error!("ws handshake with {} failed: {}", peer, e),
+                    }
+                });
+            }
+            // 回收已经结束的连接任务
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            _ = shutdown.cancelled() => break,
+        }
+    }
+
+    // 停止接收新连接，等待现有连接发送 Close 后退出
+    drop(incoming);
+    drop(bound);
+    closing.cancel();
+    info!("ws_server draining {} connections", connections.len());
+    while connections.join_next().await.is_some() {}
+    if let Some(e) = failed {
+        return Err(e).context("accept failed");
     }
+    info!("ws_server stopped");
     Ok(())
 }
@@ -0,0 +1,310 @@
+// sqlite_sample 的集成测试：每个测试打开自己的内存数据库，互不影响
+use panorama_s::common::error::AppError;
+use panorama_s::common::secret::Secret;
+use panorama_s::sqlite_sample::kv_store::KvStore;
+use panorama_s::sqlite_sample::migrate;
+use panorama_s::sqlite_sample::repository::Repository;
+use panorama_s::sqlite_sample::sqlite_c::SqliteCrud;
+use panorama_s::sqlite_sample::user_service::{NewUser, UserService, UserUpdate};
+use panorama_s::sqlite_sample::users_po::{User, UserStatus};
+use panorama_s::use_sqlite;
+use std::sync::Arc;
+
+// 表由迁移创建
+fn open_db() -> SqliteCrud {
+    let db = SqliteCrud::open_in_memory().expect("open in-memory db");
+    migrate::apply(&db).expect("apply migrations");
+    db
+}
+
+fn table_names(db: &SqliteCrud) -> Vec<String> {
+    let conn = db.read().unwrap();
+    let mut stmt = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+        .unwrap();
+    let names = stmt.query_map([], |row| row.get(0)).unwrap();
+    names.collect::<rusqlite::Result<Vec<String>>>().unwrap()
+}
+
+fn new_user(username: &str, age: i32) -> User {
+    User {
+        username: username.to_string(),
+        email: Some(format!("{}@example.com", username)),
+        name: username.to_string(),
+        age,
+        created_at: 1,
+        updated_at: 1,
+        ..Default::default()
+    }
+}
+
+// create_table
+
+#[test]
+fn create_table_creates_all_tables() {
+    let db = open_db();
+    let tables = table_names(&db);
+    for table in [
+        "table_test",
+        "users",
+        "products",
+        "accounts",
+        "changes",
+        "schema_version",
+    ] {
+        assert!(
+            tables.iter().any(|t| t == table),
+            "missing {} in {:?}",
+            table,
+            tables
+        );
+    }
+    let conn = db.read().unwrap();
+    assert_eq!(
+        migrate::current_version(&conn).unwrap(),
+        migrate::latest_version()
+    );
+}
+
+#[test]
+fn create_table_is_idempotent() {
+    let db = open_db();
+    assert!(migrate::apply(&db).unwrap().is_empty());
+    migrate::ensure_current(&db).unwrap();
+}
+
+#[test]
+fn in_memory_databases_are_isolated() {
+    let a = open_db();
+    let b = open_db();
+    use_sqlite::insert_data(&a, "k", "v").unwrap();
+    assert!(matches!(
+        use_sqlite::query_data(&b, "k"),
+        Err(AppError::NotFound(_))
+    ));
+}
+
+// insert_data / query_data
+
+#[test]
+fn insert_data_then_query_data() {
+    let db = open_db();
+    use_sqlite::insert_data(&db, "aaa", "aaa_value").unwrap();
+    assert_eq!(use_sqlite::query_data(&db, "aaa").unwrap(), "aaa_value");
+}
+
+#[test]
+fn insert_data_overwrites_existing_key() {
+    let db = open_db();
+    use_sqlite::insert_data(&db, "k", "v1").unwrap();
+    use_sqlite::insert_data(&db, "k", "v2").unwrap();
+    assert_eq!(use_sqlite::query_data(&db, "k").unwrap(), "v2");
+
+    let entry = KvStore::new(&db).get("k").unwrap();
+    assert_eq!(entry.version, 2);
+    let count: i64 = db
+        .read()
+        .unwrap()
+        .query_row(
+            "SELECT COUNT(*) FROM table_test WHERE key = 'k'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn query_data_missing_key_is_not_found() {
+    let db = open_db();
+    assert!(matches!(
+        use_sqlite::query_data(&db, "missing"),
+        Err(AppError::NotFound(_))
+    ));
+}
+
+#[test]
+fn delete_data_removes_key() {
+    let db = open_db();
+    use_sqlite::insert_data(&db, "k", "v").unwrap();
+    assert_eq!(use_sqlite::delete_data(&db, "k").unwrap(), 1);
+    assert_eq!(use_sqlite::delete_data(&db, "k").unwrap(), 0);
+    assert!(matches!(
+        use_sqlite::query_data(&db, "k"),
+        Err(AppError::NotFound(_))
+    ));
+}
+
+#[test]
+fn use_sqlite_sample_runs() {
+    let db = open_db();
+    use_sqlite::use_sqlite(&db).unwrap();
+    assert_eq!(use_sqlite::query_data(&db, "aaa").unwrap(), "aaa_value");
+}
+
+// users_po::User CRUD
+
+#[test]
+fn user_insert_and_get() {
+    let db = open_db();
+    let repo = Repository::<User>::new(&db);
+    let id = repo.insert(&new_user("alice", 20)).unwrap();
+
+    let user = repo.get(id).unwrap();
+    assert_eq!(user.id, id);
+    assert_eq!(user.username, "alice");
+    assert_eq!(user.email.as_deref(), Some("alice@example.com"));
+    assert_eq!(user.age, 20);
+    assert_eq!(user.status, UserStatus::Active);
+    assert!(user.password_hash.is_none());
+}
+
+#[test]
+fn user_get_missing_is_not_found() {
+    let db = open_db();
+    assert!(matches!(
+        Repository::<User>::new(&db).get(42),
+        Err(AppError::NotFound(_))
+    ));
+}
+
+#[test]
+fn user_list_is_ordered_by_id() {
+    let db = open_db();
+    let repo = Repository::<User>::new(&db);
+    let bob = repo.insert(&new_user("bob", 30)).unwrap();
+    let alice = repo.insert(&new_user("alice", 20)).unwrap();
+
+    let ids: Vec<i64> = repo.list().unwrap().iter().map(|u| u.id).collect();
+    assert_eq!(ids, vec![bob, alice]);
+}
+
+#[test]
+fn user_find_by_username_ignores_case() {
+    let db = open_db();
+    let repo = Repository::<User>::new(&db);
+    let id = repo.insert(&new_user("Alice", 20)).unwrap();
+    assert_eq!(repo.find_one_by("username", &"alice").unwrap().id, id);
+}
+
+#[test]
+fn user_find_by_unknown_column_is_rejected() {
+    let db = open_db();
+    assert!(matches!(
+        Repository::<User>::new(&db).find_one_by("password_hash; --", &"x"),
+        Err(AppError::Internal(_))
+    ));
+}
+
+#[test]
+fn user_update() {
+    let db = open_db();
+    let repo = Repository::<User>::new(&db);
+    let id = repo.insert(&new_user("alice", 20)).unwrap();
+
+    let mut user = repo.get(id).unwrap();
+    user.name = "Alice Liddell".to_string();
+    user.age = 21;
+    user.status = UserStatus::Disabled;
+    repo.update(&user).unwrap();
+
+    let updated = repo.get(id).unwrap();
+    assert_eq!(updated.name, "Alice Liddell");
+    assert_eq!(updated.age, 21);
+    assert_eq!(updated.status, UserStatus::Disabled);
+}
+
+#[test]
+fn user_update_missing_is_not_found() {
+    let db = open_db();
+    let mut user = new_user("ghost", 20);
+    user.id = 42;
+    assert!(matches!(
+        Repository::<User>::new(&db).update(&user),
+        Err(AppError::NotFound(_))
+    ));
+}
+
+#[test]
+fn user_delete() {
+    let db = open_db();
+    let repo = Repository::<User>::new(&db);
+    let id = repo.insert(&new_user("alice", 20)).unwrap();
+
+    repo.delete(id).unwrap();
+    assert!(matches!(repo.get(id), Err(AppError::NotFound(_))));
+    assert!(matches!(repo.delete(id), Err(AppError::NotFound(_))));
+}
+
+#[test]
+fn user_duplicate_username_or_email_conflicts() {
+    let db = open_db();
+    let repo = Repository::<User>::new(&db);
+    repo.insert(&new_user("alice", 20)).unwrap();
+
+    assert!(matches!(
+        repo.insert(&new_user("ALICE", 30)),
+        Err(AppError::Conflict(_))
+    ));
+    let mut same_email = new_user("alice2", 30);
+    same_email.email = Some("Alice@Example.com".to_string());
+    assert!(matches!(
+        repo.insert(&same_email),
+        Err(AppError::Conflict(_))
+    ));
+}
+
+#[test]
+fn user_changes_inside_failed_transaction_are_rolled_back() {
+    let db = open_db();
+    let result: Result<(), AppError> = db.transaction(|tx| {
+        Repository::<User>::in_tx(tx).insert(&new_user("alice", 20))?;
+        Err(AppError::Validation("abort".into()))
+    });
+    assert!(result.is_err());
+    assert!(Repository::<User>::new(&db).list().unwrap().is_empty());
+}
+
+#[test]
+fn user_service_lifecycle() {
+    let users = UserService::new(Arc::new(open_db()));
+    let password = Secret::new("correct horse");
+    let user = users
+        .create(NewUser {
+            username: "alice".to_string(),
+            email: Some("Alice@Example.com".to_string()),
+            name: None,
+            age: 20,
+            password: Some(password.clone()),
+        })
+        .unwrap();
+    assert_eq!(user.email.as_deref(), Some("alice@example.com"));
+    assert_eq!(user.name, "alice");
+
+    assert_eq!(users.authenticate("ALICE", &password).unwrap().id, user.id);
+    assert!(matches!(
+        users.authenticate("alice", &Secret::new("wrong password")),
+        Err(AppError::Unauthorized(_))
+    ));
+
+    let updated = users
+        .update(
+            user.id,
+            UserUpdate {
+                age: Some(21),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(updated.age, 21);
+    assert!(updated.updated_at >= user.updated_at);
+
+    users.set_status(user.id, UserStatus::Disabled).unwrap();
+    assert!(matches!(
+        users.authenticate("alice", &password),
+        Err(AppError::Unauthorized(_))
+    ));
+
+    users.delete(user.id).unwrap();
+    assert!(matches!(users.get(user.id), Err(AppError::NotFound(_))));
+}
@@ -0,0 +1,135 @@
+// web_server 的集成测试：服务器绑定在随机端口上，使用测试自己打开的内存数据库
+use panorama_s::common::listen::Listener;
+use panorama_s::sqlite_sample::kv_store::KvStore;
+use panorama_s::sqlite_sample::migrate;
+use panorama_s::sqlite_sample::sqlite_c::SqliteCrud;
+use panorama_s::web_server::web_server_main;
+use panorama_utils::shutdown::ShutdownToken;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn start(db: Arc<SqliteCrud>) -> (SocketAddr, ShutdownToken, tokio::task::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = ShutdownToken::new();
+    let token = shutdown.clone();
+    let server = tokio::spawn(async move {
+        web_server_main::serve(db, vec![Listener::from(listener)], token)
+            .await
+            .unwrap();
+    });
+    (addr, shutdown, server)
+}
+
+// 发送一个 JSON 请求体的 HTTP/1.1 请求，返回状态码和响应体
+async fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+    send(addr, method, path, "application/json", body).await
+}
+
+async fn send(
+    addr: SocketAddr,
+    method: &str,
+    path: &str,
+    content_type: &str,
+    body: &str,
+) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let req = format!(
+        "{} {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\
+         Content-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(req.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, b)| b.to_string())
+        .unwrap_or_default();
+    (status, body)
+}
+
+#[tokio::test]
+async fn handlers_use_the_injected_database() {
+    let db = Arc::new(SqliteCrud::open_in_memory().unwrap());
+    migrate::apply(&db).unwrap();
+    KvStore::new(&db).set("seeded", "from test", None).unwrap();
+    let (addr, shutdown, server) = start(db.clone()).await;
+
+    let (status, body) = request(addr, "GET", "/kv/seeded", "").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("from test"), "{}", body);
+
+    let (status, _) = request(addr, "PUT", "/kv/written", r#"{"value": "over http"}"#).await;
+    assert_eq!(status, 200);
+    let entry = KvStore::new(&db).get("written").unwrap();
+    assert_eq!(entry.value.as_deref(), Some("over http"));
+
+    let (status, _) = request(addr, "GET", "/kv/missing", "").await;
+    assert_eq!(status, 404);
+    let (status, body) = request(addr, "GET", "/health/ready", "").await;
+    assert!(body.contains("\"sqlite\""), "{} {}", status, body);
+
+    shutdown.cancel();
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn create_user_accepts_form_and_json_bodies() {
+    let db = Arc::new(SqliteCrud::open_in_memory().unwrap());
+    migrate::apply(&db).unwrap();
+    let (addr, shutdown, server) = start(db).await;
+
+    let form = "application/x-www-form-urlencoded";
+    let (status, body) = send(
+        addr,
+        "POST",
+        "/users",
+        form,
+        "username=alice&password=secret-pw-1",
+    )
+    .await;
+    assert_eq!(status, 200, "{}", body);
+    assert!(body.contains("\"alice\""), "{}", body);
+    let (status, body) = request(addr, "POST", "/users", r#"{"username": "bob", "age": 30}"#).await;
+    assert_eq!(status, 200, "{}", body);
+    assert!(body.contains("\"bob\""), "{}", body);
+
+    let (status, _) = request(
+        addr,
+        "POST",
+        "/login",
+        r#"{"username": "alice", "password": "secret-pw-1"}"#,
+    )
+    .await;
+    assert_eq!(status, 200);
+
+    shutdown.cancel();
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn users_post_creates_a_user_that_can_log_in() {
+    let db = Arc::new(SqliteCrud::open_in_memory().unwrap());
+    migrate::apply(&db).unwrap();
+    let (addr, shutdown, server) = start(db).await;
+
+    let credentials = r#"{"username": "carol", "password": "secret-pw-2"}"#;
+    let (status, body) = request(addr, "POST", "/users_post", credentials).await;
+    assert_eq!(status, 200, "{}", body);
+    assert!(body.contains("user post"), "{}", body);
+    let (status, _) = request(addr, "POST", "/login", credentials).await;
+    assert_eq!(status, 200);
+    let (status, _) = request(addr, "POST", "/users_post", credentials).await;
+    assert_eq!(status, 409);
+
+    shutdown.cancel();
+    server.await.unwrap();
+}
@@ -0,0 +1,247 @@
+// 分层配置的加载：默认值 < TOML 配置文件 < 环境变量，panorama_s、panorama_c 的 Settings 共用。
+//
+// 环境变量用 __ 分隔层级，例如 PANORAMA_S__SQLITE__DB_PATH 覆盖 [sqlite] db_path。
+// 值先按 TOML 字面量解析（数字、布尔、数组），字段类型对不上时（如字符串字段 AUTH_KEY=123）
+// 改用原始字符串。
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 一个程序的配置来源
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigSource {
+    /// 默认配置文件（相对于工作目录），不存在时只用默认值
+    pub default_path: &'static str,
+    /// 指定配置文件路径的环境变量
+    pub path_env: &'static str,
+    /// 覆盖单个配置项的环境变量前缀，如 PANORAMA_S__
+    pub env_prefix: &'static str,
+}
+
+/// 加载配置。
+/// path 为 None 时依次尝试 path_env 和 default_path，默认文件不存在则只用默认值。
+/// 显式指定的文件不存在时报错。
+pub fn load<T: DeserializeOwned>(source: &ConfigSource, path: Option<&Path>) -> Result<T> {
+    let env_path = std::env::var(source.path_env).ok();
+    let (path, required) = match (path, env_path.as_deref()) {
+        (Some(p), _) => (p, true),
+        (None, Some(p)) => (Path::new(p), true),
+        (None, None) => (Path::new(source.default_path), false),
+    };
+
+    let root = if path.exists() {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("read config file {} failed", path.display()))?;
+        toml::from_str::<toml::Table>(&text)
+            .with_context(|| format!("parse config file {} failed", path.display()))?
+    } else if required {
+        anyhow::bail!("config file {} not found", path.display());
+    } else {
+        toml::Table::new()
+    };
+
+    from_table(root, source.env_prefix, std::env::vars())
+}
+
+/// 把 prefix + SECTION__KEY=value 形式的环境变量合并进 root 后转成 T
+pub fn from_table<T: DeserializeOwned>(
+    mut root: toml::Table,
+    prefix: &str,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<T> {
+    // 按字面量解析成非字符串的覆盖项，键为 a.b 形式的路径，值为原始字符串
+    let mut coerced = apply_env_overrides(&mut root, prefix, vars);
+    loop {
+        let value = toml::Value::Table(root.clone());
+        let err = match serde_path_to_error::deserialize(value) {
+            Ok(settings) => return Ok(settings),
+            Err(err) => err,
+        };
+        let path = err.path().to_string();
+        match coerced.remove(&path) {
+            // 该字段要的是字符串，改回原始字符串重试
+            Some(raw) => {
+                let keys: Vec<String> = path.split('.').map(str::to_string).collect();
+                insert(&mut root, &keys, toml::Value::String(raw));
+            }
+            None => return Err(anyhow::Error::new(err.into_inner())).context("invalid settings"),
+        }
+    }
+}
+
+// 合并环境变量，返回按字面量解析成非字符串的覆盖项
+fn apply_env_overrides(
+    root: &mut toml::Table,
+    prefix: &str,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> HashMap<String, String> {
+    let mut coerced = HashMap::new();
+    for (name, raw) in vars {
+        let Some(path) = name.strip_prefix(prefix) else {
+            continue;
+        };
+        let keys: Vec<String> = path.split("__").map(|k| k.to_lowercase()).collect();
+        if keys.iter().any(String::is_empty) {
+            continue;
+        }
+        let value = parse_env_value(&raw);
+        if !value.is_str() {
+            coerced.insert(keys.join("."), raw);
+        }
+        insert(root, &keys, value);
+    }
+    coerced
+}
+
+// 按路径写入，中间缺少或不是表的层级替换为空表
+fn insert(root: &mut toml::Table, keys: &[String], value: toml::Value) {
+    let Some((last, parents)) = keys.split_last() else {
+        return;
+    };
+    let mut table = root;
+    for key in parents {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        table = entry.as_table_mut().expect("entry is a table");
+    }
+    table.insert(last.clone(), value);
+}
+
+fn parse_env_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("v = {}", raw))
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+/// 进程级的配置：启动时 init 一次，之后通过 get 读取
+pub struct GlobalSettings<T>(OnceCell<T>);
+
+impl<T: Default> GlobalSettings<T> {
+    pub const fn new() -> Self {
+        Self(OnceCell::new())
+    }
+
+    pub fn init(&self, settings: T) -> Result<()> {
+        self.0
+            .set(settings)
+            .map_err(|_| anyhow::anyhow!("SETTINGS already initialized"))
+    }
+
+    /// 取得配置，未初始化时使用默认值
+    pub fn get(&self) -> &T {
+        self.0.get_or_init(T::default)
+    }
+}
+
+impl<T: Default> Default for GlobalSettings<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    #[serde(default)]
+    struct Settings {
+        server: Server,
+        auth_key: String,
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    #[serde(default)]
+    struct Server {
+        port: u16,
+        enabled: bool,
+        listen: Vec<String>,
+        name: String,
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn load_from(file: &str, env: &[(&str, &str)]) -> Result<Settings> {
+        from_table(toml::from_str(file).unwrap(), "APP__", vars(env))
+    }
+
+    #[test]
+    fn parses_env_values_as_toml_literals() {
+        assert_eq!(parse_env_value("42"), toml::Value::Integer(42));
+        assert_eq!(parse_env_value("true"), toml::Value::Boolean(true));
+        assert_eq!(
+            parse_env_value(r#"["a", "b"]"#),
+            toml::Value::Array(vec!["a".into(), "b".into()])
+        );
+        assert_eq!(parse_env_value("ws://host:1"), "ws://host:1".into());
+        assert_eq!(parse_env_value(""), "".into());
+    }
+
+    #[test]
+    fn env_overrides_file_values() {
+        let settings = load_from(
+            "auth_key = \"file\"\n[server]\nport = 1\nname = \"file\"",
+            &[
+                ("APP__SERVER__PORT", "8080"),
+                ("APP__SERVER__ENABLED", "true"),
+                ("APP__SERVER__LISTEN", r#"["0.0.0.0:1", "unix:/a.sock"]"#),
+                ("OTHER__SERVER__PORT", "9"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(settings.server.port, 8080);
+        assert!(settings.server.enabled);
+        assert_eq!(settings.server.listen, ["0.0.0.0:1", "unix:/a.sock"]);
+        assert_eq!(settings.server.name, "file");
+        assert_eq!(settings.auth_key, "file");
+    }
+
+    #[test]
+    fn string_fields_keep_numeric_and_bool_values_as_strings() {
+        let settings = load_from(
+            "",
+            &[
+                ("APP__AUTH_KEY", "123"),
+                ("APP__SERVER__NAME", "true"),
+                ("APP__SERVER__PORT", "7"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(settings.auth_key, "123");
+        assert_eq!(settings.server.name, "true");
+        assert_eq!(settings.server.port, 7);
+    }
+
+    #[test]
+    fn reports_the_invalid_field() {
+        let err = load_from("", &[("APP__SERVER__PORT", "http")]).unwrap_err();
+        assert!(format!("{:#}", err).contains("server.port"), "{:#}", err);
+        let err = load_from("", &[("APP__SERVER__PORT", "70000")]).unwrap_err();
+        assert!(format!("{:#}", err).contains("server.port"), "{:#}", err);
+    }
+
+    #[test]
+    fn missing_explicit_file_is_an_error() {
+        let source = ConfigSource {
+            default_path: "no-such-config.toml",
+            path_env: "PANORAMA_UTILS_TEST_CONFIG",
+            env_prefix: "PANORAMA_UTILS_TEST__",
+        };
+        let defaults: Settings = load(&source, None).unwrap();
+        assert_eq!(defaults, Settings::default());
+        assert!(load::<Settings>(&source, Some(Path::new("no-such-config.toml"))).is_err());
+    }
+}
@@ -0,0 +1,4 @@
+// panorama_s、panorama_c 共用的工具
+pub mod config;
+pub mod logging;
+pub mod shutdown;
@@ -0,0 +1,149 @@
+// 日志初始化：读取 log4rs yaml 配置，文件不存在时使用内置配置；运行时可以按模块调整级别
+use anyhow::{Context, Result};
+use log::{warn, LevelFilter};
+use log4rs::config::{Config, Deserializers, Logger, RawConfig};
+use log4rs::Handle;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// 内置的默认配置
+pub const DEFAULT_CONFIG: &str = include_str!("default_log4rs.yaml");
+
+/// levels() 返回结果中代表 root logger 的名字
+pub const ROOT: &str = "root";
+
+/// 运行时调整日志级别的句柄，可以 clone 后在别处使用
+#[derive(Clone)]
+pub struct LogControl {
+    inner: Arc<Mutex<LogState>>,
+}
+
+struct LogState {
+    handle: Handle,
+    spec: LogSpec,
+}
+
+#[derive(Clone)]
+struct LogSpec {
+    /// 配置文件内容（或内置配置），每次调整级别都从这里重新生成 Config
+    source: String,
+    root_level: Option<LevelFilter>,
+    module_levels: BTreeMap<String, LevelFilter>,
+}
+
+impl LogSpec {
+    fn raw(&self) -> Result<RawConfig> {
+        serde_yaml::from_str(&self.source).context("parse log4rs config failed")
+    }
+
+    fn build(&self) -> Result<Config> {
+        let raw = self.raw()?;
+        let (appenders, mut errors) = raw.appenders_lossy(&Deserializers::default());
+        // 与 log4rs::init_file 相同：有问题的 appender 打印到 stderr 后跳过
+        errors.handle();
+
+        let mut root = raw.root();
+        if let Some(level) = self.root_level {
+            root.set_level(level);
+        }
+
+        // 配置文件里已有的 logger 保留 appender 和 additive，只替换级别
+        let mut loggers: BTreeMap<String, Logger> = raw
+            .loggers()
+            .into_iter()
+            .map(|l| (l.name().to_string(), l))
+            .collect();
+        for (name, level) in &self.module_levels {
+            let logger = match loggers.get(name) {
+                Some(l) => Logger::builder()
+                    .appenders(l.appenders().iter().cloned())
+                    .additive(l.additive())
+                    .build(name.clone(), *level),
+                None => Logger::builder().build(name.clone(), *level),
+            };
+            loggers.insert(name.clone(), logger);
+        }
+
+        let config = Config::builder()
+            .appenders(appenders)
+            .loggers(loggers.into_values())
+            .build(root)?;
+        Ok(config)
+    }
+}
+
+/// 按 log4rs 配置文件初始化日志，文件不存在时使用内置配置。
+/// level 不为 None 时覆盖 root 级别。
+pub fn init_logging(config_path: &str, level: Option<LevelFilter>) -> Result<LogControl> {
+    let path = Path::new(config_path);
+    let exists = path.exists();
+    let source = if exists {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("read log4rs config {} failed", config_path))?
+    } else {
+        DEFAULT_CONFIG.to_string()
+    };
+
+    let spec = LogSpec {
+        source,
+        root_level: level,
+        module_levels: BTreeMap::new(),
+    };
+    let handle = log4rs::init_config(spec.build()?)?;
+
+    if !exists {
+        warn!(
+            "[log] {} not found, using built-in console logging",
+            config_path
+        );
+    }
+    Ok(LogControl {
+        inner: Arc::new(Mutex::new(LogState { handle, spec })),
+    })
+}
+
+impl LogControl {
+    /// 当前生效的级别：root 以及配置了级别的各模块
+    pub fn levels(&self) -> Result<BTreeMap<String, LevelFilter>> {
+        let spec = self.lock().spec.clone();
+        let raw = spec.raw()?;
+        let mut levels: BTreeMap<String, LevelFilter> = raw
+            .loggers()
+            .into_iter()
+            .map(|l| (l.name().to_string(), l.level()))
+            .collect();
+        levels.extend(spec.module_levels);
+        levels.insert(
+            ROOT.to_string(),
+            spec.root_level.unwrap_or_else(|| raw.root().level()),
+        );
+        Ok(levels)
+    }
+
+    /// 设置 root（target 为 "root"）或某个模块（如 panorama_s::web_socket）的级别。
+    /// level 为 None 时去掉运行时设置，恢复配置文件中的级别。
+    pub fn set_level(&self, target: &str, level: Option<LevelFilter>) -> Result<()> {
+        let mut state = self.lock();
+        // 在副本上修改，生成配置成功后再替换，失败时保持原状
+        let mut spec = state.spec.clone();
+        if target == ROOT {
+            spec.root_level = level;
+        } else {
+            match level {
+                Some(level) => spec.module_levels.insert(target.to_string(), level),
+                None => spec.module_levels.remove(target),
+            };
+        }
+
+        state.handle.set_config(spec.build()?);
+        state.spec = spec;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LogState> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
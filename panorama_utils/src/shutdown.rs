@@ -0,0 +1,105 @@
+// 关闭协调：收到退出信号后 cancel，各任务 await cancelled() 后停止接收新连接并排空
+use log::info;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// 可 clone 的关闭令牌，任意一个 clone 调用 cancel 后所有 clone 都会收到通知
+#[derive(Debug, Clone)]
+pub struct ShutdownToken {
+    tx: Arc<watch::Sender<bool>>,
+    rx: watch::Receiver<bool>,
+}
+
+impl ShutdownToken {
+    pub fn new() -> Self {
+        let (tx, rx) = watch::channel(false);
+        Self {
+            tx: Arc::new(tx),
+            rx,
+        }
+    }
+
+    /// 通知所有持有者开始关闭，可以重复调用
+    pub fn cancel(&self) {
+        self.tx.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// 等待关闭通知，已经关闭时立即返回
+    pub async fn cancelled(&self) {
+        let mut rx = self.rx.clone();
+        // 发送端由令牌自己持有，changed() 不会因为发送端被 drop 而返回错误
+        while !*rx.borrow_and_update() {
+            if rx.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+impl Default for ShutdownToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 触发关闭的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownReason {
+    Interrupt,
+    Terminate,
+    Hangup,
+    /// 某个持有令牌的任务主动 cancel
+    Cancelled,
+}
+
+impl fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ShutdownReason::Interrupt => "SIGINT",
+            ShutdownReason::Terminate => "SIGTERM",
+            ShutdownReason::Hangup => "SIGHUP",
+            ShutdownReason::Cancelled => "cancelled",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 等待 SIGINT/SIGTERM/SIGHUP 之一
+#[cfg(unix)]
+pub async fn wait_for_signal() -> io::Result<ShutdownReason> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    let reason = tokio::select! {
+        _ = interrupt.recv() => ShutdownReason::Interrupt,
+        _ = terminate.recv() => ShutdownReason::Terminate,
+        _ = hangup.recv() => ShutdownReason::Hangup,
+    };
+    Ok(reason)
+}
+
+/// 非 unix 平台只支持 Ctrl+C
+#[cfg(not(unix))]
+pub async fn wait_for_signal() -> io::Result<ShutdownReason> {
+    tokio::signal::ctrl_c().await?;
+    Ok(ShutdownReason::Interrupt)
+}
+
+/// 等待退出信号或 token 被主动 cancel，然后 cancel token 通知所有任务
+pub async fn graceful_shutdown(token: &ShutdownToken) -> io::Result<ShutdownReason> {
+    let reason = tokio::select! {
+        res = wait_for_signal() => res?,
+        _ = token.cancelled() => ShutdownReason::Cancelled,
+    };
+    info!("shutdown requested: {}", reason);
+    token.cancel();
+    Ok(reason)
+}
@@ -4,6 +4,8 @@ cd ./panorama_s
 clear
 # cargo run --quiet -p panorama_s
 # cargo run -p panorama_s -- --auth-key xxx
+# cargo run -p panorama_s -- --config config.toml --log-level info serve
+# cargo run -p panorama_s -- kv get aaa
 # RUSTFLAGS="-A unused" cargo run -p panorama_s # 屏蔽警告
 cargo run -p panorama_s
 cd ..